@@ -1,16 +1,42 @@
-//! Parses the request body as json if the content-type: json header is set.
+//! Buffers the request body if the content-type has a decoder, see
+//! `Decoders`, or is `application/x-www-form-urlencoded`. Read it with
+//! `Request::body_as()`, `Request::json()` or `Request::form_as()`.
 //! Note that if there are any errors in parsing the json
 //! it will forward an error response to the client.
 //! It is recommended you mount this middleware on the root `Router`
-use core::Request;
+//!
+//! Bodies aren't limited by default, see `BodyLimit` to reject the larger
+//! ones with a `413 Payload Too Large`.
+use super::decoders::{Decoder, Decoders};
+use core::{Request, Response};
 use futures::{future, Future, Stream};
-use hyper::{self, header::CONTENT_TYPE};
+use hyper::{
+	self,
+	header::{CONTENT_LENGTH, CONTENT_TYPE},
+	Chunk,
+};
 use proto::{MiddleWare, MiddleWareFuture};
-use std::ops::Deref;
+use std::{ops::Deref, sync::Arc};
 
-pub(crate) struct Json(hyper::Chunk);
+/// A body buffered for one of the `Decoders`.
+pub(crate) struct Buffered {
+	pub(crate) body: hyper::Chunk,
+	/// the media type the decoder was registered for.
+	pub(crate) mime: String,
+	pub(crate) decoder: Arc<Decoder>,
+}
 
-impl Deref for Json {
+impl Deref for Buffered {
+	type Target = hyper::Chunk;
+
+	fn deref(&self) -> &Self::Target {
+		&self.body
+	}
+}
+
+pub(crate) struct UrlEncoded(hyper::Chunk);
+
+impl Deref for UrlEncoded {
 	type Target = hyper::Chunk;
 
 	fn deref(&self) -> &Self::Target {
@@ -18,52 +44,287 @@ impl Deref for Json {
 	}
 }
 
-/// Json Body Parser.
+/// The most bytes the `BodyParser` buffers for a request, there's no limit
+/// by default.
+///
+/// It's set with `ArcReactor::body_limit`, `Router::body_limit` or
+/// `Route::body_limit`, the innermost one applies. It can also be mounted as a
+/// request middleware.
+///
+/// A `BodyParser` mounted on the `ArcReactor` waits for the `Router` to find
+/// the route before it reads the body, so the limits of the `Router` and the
+/// `Route` are enforced while the body streams in, and a request whose
+/// `Content-Length` is over the limit is rejected without reading it.
+///
+/// ```rust, ignore
+/// let routes = Router::new()
+/// 	.body_limit(64 * 1024)
+/// 	.post("/comments", CommentService)
+/// 	.post("/import", Route::new(ImportService).body_limit(32 * 1024 * 1024));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BodyLimit(pub u64);
+
+impl MiddleWare<Request> for BodyLimit {
+	fn call(&self, mut req: Request) -> MiddleWareFuture<Request> {
+		match apply_body_limit(&mut req, self.0) {
+			Ok(()) => Box::new(future::ok(req)),
+			Err(res) => Box::new(future::err(res)),
+		}
+	}
+}
+
+/// Set on the requests of an `ArcReactor` that serves a `Router`, until the
+/// `Router` gets them.
+#[derive(Clone, Debug)]
+pub(crate) struct BeforeRouting;
+
+impl MiddleWare<Request> for BeforeRouting {
+	fn call(&self, mut req: Request) -> MiddleWareFuture<Request> {
+		req.set(BeforeRouting);
+		Box::new(future::ok(req))
+	}
+}
+
+/// Set by a `BodyParser` that ran before routing, the `Router` reads the body
+/// once it has found the route, with the route's `BodyLimit`.
+pub(crate) struct DeferredBody;
+
+fn too_large(limit: u64) -> Response {
+	error!("request body is larger than {} bytes", limit);
+	let error = json!({
+		"error": format!("Request body is larger than {} bytes", limit)
+	});
+	(413, error).into()
+}
+
+/// Applies the limit to the request, if the body was already buffered by a
+/// `BodyParser` its size is checked right away.
+pub(crate) fn apply_body_limit(req: &mut Request, limit: u64) -> Result<(), Response> {
+	req.set(BodyLimit(limit));
+
+	let buffered = req
+		.get::<Buffered>()
+		.map(|body| body.len())
+		.or_else(|| req.get::<UrlEncoded>().map(|form| form.len()));
+	match buffered {
+		Some(len) if len as u64 > limit => Err(too_large(limit)),
+		_ => Ok(()),
+	}
+}
+
+/// Reads the whole body, failing as soon as it's larger than `limit`.
+fn read(req: &mut Request, limit: Option<u64>) -> impl Future<Item = Chunk, Error = Response> {
+	req.body()
+		.map_err(|err| {
+			error!("Error reading body from client: {}", err);
+			let error = json!({
+				"error": "Could not read request payload"
+			});
+			(400, error).into()
+		})
+		.fold(Vec::new(), move |mut body, chunk| {
+			if let Some(limit) = limit {
+				if (body.len() + chunk.len()) as u64 > limit {
+					return Err(too_large(limit));
+				}
+			}
+			body.extend_from_slice(&chunk);
+			Ok(body)
+		})
+		.map(Chunk::from)
+}
+
+/// Body Parser for the media types in `Decoders`, and url-encoded forms.
 ///
+/// When it's mounted on an `ArcReactor` that serves a `Router`, the body is
+/// read once the route is found rather than right away, so the middlewares
+/// mounted after it on the `ArcReactor` can't read the body yet.
 #[derive(Clone, Debug)]
 pub struct BodyParser;
 
 impl MiddleWare<Request> for BodyParser {
 	fn call(&self, mut req: Request) -> MiddleWareFuture<Request> {
-		let mut isJson = false;
+		// the body was already read, by a `BodyParser` mounted higher up.
+		if req.get::<Buffered>().is_some() || req.get::<UrlEncoded>().is_some() {
+			return Box::new(future::ok(req));
+		}
+
+		// the limit isn't known until the route is.
+		if req.get::<BeforeRouting>().is_some() {
+			req.set(DeferredBody);
+			return Box::new(future::ok(req));
+		}
+
+		let (mut decoder, mut isForm) = (None, false);
 		{
 			if let Some(ct) = req.headers().get(CONTENT_TYPE) {
-				isJson = {
-					let ct = ct.to_str();
-					ct.is_ok() && ct.unwrap().contains("json")
-				};
+				if let Ok(ct) = ct.to_str() {
+					isForm = ct.starts_with("application/x-www-form-urlencoded");
+					decoder = match req.get::<Decoders>() {
+						Some(decoders) => decoders.find(ct),
+						None => Decoders::default().find(ct),
+					};
+				}
 			}
 		}
 
-		if !isJson {
+		if decoder.is_none() && !isForm {
 			return Box::new(future::ok(req));
 		}
 
-		let read_body_future = req.body().concat2().then(|result| {
-			let json = match result {
-				Ok(chunk) => {
-					// assert that the chunk length is > 0
-					// otherwise bad request.
-					if chunk.len() == 0 {
-						error!("zero-length json body");
-						let error = json!({ "error": "Empty request body" });
-						return Err((400, error).into());
-					}
-					Json(chunk)
-				}
-				Err(err) => {
-					error!("Error reading json body from client: {}", err);
-					let error = json!({
-						"error": "Could not read request payload"
-					});
-					return Err((400, error).into());
-				}
-			};
+		let limit = req.get::<BodyLimit>().map(|limit| limit.0);
+		let len = req
+			.headers()
+			.get(CONTENT_LENGTH)
+			.and_then(|len| len.to_str().ok())
+			.and_then(|len| len.parse::<u64>().ok());
+		if let (Some(len), Some(limit)) = (len, limit) {
+			if len > limit {
+				return Box::new(future::err(too_large(limit)));
+			}
+		}
+
+		let read_body_future = read(&mut req, limit).and_then(move |chunk| {
+			if isForm {
+				// an empty body is an empty form.
+				req.set(UrlEncoded(chunk));
+				return Ok(req);
+			}
 
-			req.set(json);
+			// assert that the chunk length is > 0
+			// otherwise bad request.
+			if chunk.len() == 0 {
+				error!("zero-length request body");
+				let error = json!({ "error": "Empty request body" });
+				return Err((400, error).into());
+			}
+
+			if let Some((mime, decoder)) = decoder {
+				req.set(Buffered {
+					body: chunk,
+					mime,
+					decoder,
+				});
+			}
 			Ok(req)
 		});
 
 		return Box::new(read_body_future);
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::stream;
+	use http::request::Builder;
+	use hyper::Body;
+	use std::collections::HashMap;
+	use tokio::runtime::Runtime;
+
+	#[test]
+	fn it_parses_url_encoded_forms() {
+		let req: Request = Builder::new()
+			.method("POST")
+			.uri("/login")
+			.header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+			.body(Body::from("user=seun&password=hunter%202"))
+			.unwrap()
+			.into();
+
+		let req = Runtime::new()
+			.unwrap()
+			.block_on(BodyParser.call(req))
+			.unwrap();
+		let form: HashMap<String, String> = req.form_as().unwrap();
+
+		assert_eq!(form["user"], "seun");
+		assert_eq!(form["password"], "hunter 2");
+		assert!(req.json::<HashMap<String, String>>().is_err());
+	}
+
+	#[test]
+	fn it_limits_the_body_while_streaming() {
+		// no Content-Length, the body is chunked.
+		let chunks = vec![vec![b'a'; 16]; 4];
+		let body = Body::wrap_stream(stream::iter_ok::<_, hyper::Error>(chunks));
+		let req: Request = Builder::new()
+			.method("POST")
+			.uri("/comments")
+			.header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+			.body(body)
+			.unwrap()
+			.into();
+
+		let mut runtime = Runtime::new().unwrap();
+		let req = runtime.block_on(BodyLimit(32).call(req)).unwrap();
+		let res = match runtime.block_on(BodyParser.call(req)) {
+			Ok(_) => panic!("the body should have been rejected"),
+			Err(res) => res,
+		};
+		assert_eq!(res.status().as_u16(), 413);
+
+		let req: Request = Builder::new()
+			.method("POST")
+			.uri("/comments")
+			.header(CONTENT_TYPE, "application/json")
+			.header(CONTENT_LENGTH, "2048")
+			.body(Body::empty())
+			.unwrap()
+			.into();
+		let req = runtime.block_on(BodyLimit(1024).call(req)).unwrap();
+		let res = match runtime.block_on(BodyParser.call(req)) {
+			Ok(_) => panic!("the body should have been rejected"),
+			Err(res) => res,
+		};
+		assert_eq!(res.status().as_u16(), 413);
+	}
+
+	#[test]
+	fn it_reads_json_from_any_json_content_type() {
+		let mut runtime = Runtime::new().unwrap();
+
+		for content_type in &["text/json", "application/x-json; charset=utf-8"] {
+			let req: Request = Builder::new()
+				.method("POST")
+				.uri("/users")
+				.header(CONTENT_TYPE, *content_type)
+				.body(Body::from(r#"{ "name": "seun" }"#))
+				.unwrap()
+				.into();
+			let req = runtime.block_on(BodyParser.call(req)).unwrap();
+			let user: HashMap<String, String> = req.json().unwrap();
+			assert_eq!(user["name"], "seun", "{}", content_type);
+		}
+	}
+
+	#[test]
+	fn it_decodes_by_content_type() {
+		let mut runtime = Runtime::new().unwrap();
+
+		let req: Request = Builder::new()
+			.method("POST")
+			.uri("/users")
+			.header(CONTENT_TYPE, "application/vnd.api+json")
+			.body(Body::from(r#"{ "name": "seun" }"#))
+			.unwrap()
+			.into();
+		let req = runtime.block_on(BodyParser.call(req)).unwrap();
+		let user: HashMap<String, String> = req.body_as().unwrap();
+		assert_eq!(user["name"], "seun");
+
+		let req: Request = Builder::new()
+			.method("POST")
+			.uri("/users")
+			.header(CONTENT_TYPE, "application/json")
+			.body(Body::from(r#"{ "name": "seun" }"#))
+			.unwrap()
+			.into();
+		let decoders = Decoders::new().remove("application/json");
+		let req = runtime.block_on(decoders.call(req)).unwrap();
+		let req = runtime.block_on(BodyParser.call(req)).unwrap();
+		let res: Response = req.body_as::<HashMap<String, String>>().unwrap_err().into();
+		assert_eq!(res.status().as_u16(), 415);
+	}
+}
@@ -0,0 +1,476 @@
+use brotli::CompressorWriter;
+use core::{qualities, Response};
+use flate2::{
+	write::{GzEncoder, ZlibEncoder},
+	Compression as Level,
+};
+use futures::{future, prelude::*};
+use hyper::{
+	header::{
+		HeaderValue,
+		ACCEPT_ENCODING,
+		ACCEPT_RANGES,
+		CACHE_CONTROL,
+		CONTENT_ENCODING,
+		CONTENT_LENGTH,
+		CONTENT_TYPE,
+		ETAG,
+		VARY,
+	},
+	Body,
+	Chunk,
+	Method,
+};
+use mime::Mime;
+use mime_guess::guess_mime_type;
+use proto::{MiddleWare, MiddleWareFuture};
+use std::{
+	error::Error,
+	io::{self, Write},
+	mem,
+};
+
+/// The content-codings the `Compression` middleware can produce.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+	Brotli,
+	Gzip,
+	Deflate,
+}
+
+impl Encoding {
+	/// The `Content-Encoding` token for this encoding.
+	pub fn as_str(&self) -> &'static str {
+		match *self {
+			Encoding::Brotli => "br",
+			Encoding::Gzip => "gzip",
+			Encoding::Deflate => "deflate",
+		}
+	}
+}
+
+/// Picks the encoding the client prefers from an `Accept-Encoding` header
+/// value, according to its q-values. Ties are broken by the order of
+/// `supported`. Returns `None` if none of them are acceptable.
+pub(crate) fn preferred_encoding(accept: &str, supported: &[Encoding]) -> Option<Encoding> {
+	let mut wildcard = None;
+	let mut codings = Vec::new();
+
+	for (coding, quality) in qualities(accept) {
+		if coding == "*" {
+			wildcard = Some(quality);
+		} else {
+			codings.push((coding, quality));
+		}
+	}
+
+	let mut preferred: Option<(Encoding, f32)> = None;
+	for encoding in supported {
+		let quality = codings
+			.iter()
+			.find(|&&(ref coding, _)| {
+				coding == encoding.as_str() || (*encoding == Encoding::Gzip && coding == "x-gzip")
+			})
+			.map(|&(_, quality)| quality)
+			.or(wildcard)
+			.unwrap_or(0.0);
+
+		if quality <= 0.0 {
+			continue;
+		}
+
+		match preferred {
+			Some((_, best)) if best >= quality => {}
+			_ => preferred = Some((*encoding, quality)),
+		}
+	}
+
+	preferred.map(|(encoding, _)| encoding)
+}
+
+/// A `MiddleWare<Response>` that compresses response bodies on the fly,
+/// using the encoding negotiated from the request's `Accept-Encoding` header.
+///
+/// Responses are left untouched if they already have a `Content-Encoding`,
+/// are smaller than `min_size`, have a `Cache-Control: no-transform` or a
+/// content type that is already compressed, e.g images, videos and archives.
+/// A compressed response loses its `Accept-Ranges` header and its `ETag` is
+/// made weak.
+///
+/// ```rust, ignore
+/// let server = ArcReactor::new()
+/// 	.routes(routes)
+/// 	.after(Compression::new())
+/// 	.port(3000)
+/// 	.start();
+/// ```
+///
+/// The request headers are read from the response's `RequestHead`, so
+/// services should build their responses with `Response::for_request` or
+/// reuse the response they're given.
+#[derive(Clone, Debug)]
+pub struct Compression {
+	encodings: Vec<Encoding>,
+	min_size: u64,
+	level: u32,
+}
+
+impl Compression {
+	/// Creates a `Compression` middleware that supports brotli, gzip and
+	/// deflate, and skips bodies smaller than 1KB.
+	pub fn new() -> Self {
+		Self {
+			encodings: vec![Encoding::Brotli, Encoding::Gzip, Encoding::Deflate],
+			min_size: 1024,
+			level: 6,
+		}
+	}
+
+	/// Sets the supported encodings, in order of preference.
+	pub fn encodings<I: IntoIterator<Item = Encoding>>(mut self, encodings: I) -> Self {
+		self.encodings = encodings.into_iter().collect();
+		self
+	}
+
+	/// Bodies with a `Content-Length` smaller than this aren't compressed.
+	pub fn min_size(mut self, bytes: u64) -> Self {
+		self.min_size = bytes;
+		self
+	}
+
+	/// Sets the compression level, from 0 to 9.
+	pub fn level(mut self, level: u32) -> Self {
+		self.level = level.min(9);
+		self
+	}
+
+	/// Returns true if the response is a candidate for compression,
+	/// regardless of what the client accepts.
+	fn is_compressible(&self, res: &Response) -> bool {
+		let headers = res.headers();
+		let status = res.status();
+
+		// a partial response is a range of the uncompressed representation.
+		if status.is_informational()
+			|| status.as_u16() == 204
+			|| status.as_u16() == 206
+			|| status.as_u16() == 304
+			|| headers.contains_key(CONTENT_ENCODING)
+		{
+			return false;
+		}
+
+		let no_transform = headers
+			.get_all(CACHE_CONTROL)
+			.iter()
+			.filter_map(|value| value.to_str().ok())
+			.any(|value| value.to_lowercase().contains("no-transform"));
+		if no_transform {
+			return false;
+		}
+
+		let length = headers
+			.get(CONTENT_LENGTH)
+			.and_then(|value| value.to_str().ok())
+			.and_then(|value| value.parse::<u64>().ok());
+		if let Some(length) = length {
+			if length < self.min_size {
+				return false;
+			}
+		}
+
+		let mime_type = headers
+			.get(CONTENT_TYPE)
+			.and_then(|value| value.to_str().ok())
+			.and_then(|value| value.parse::<Mime>().ok())
+			.or_else(|| res.request().map(|head| guess_mime_type(head.uri.path())));
+
+		match mime_type {
+			Some(mime_type) => !is_compressed(&mime_type),
+			None => true,
+		}
+	}
+}
+
+impl Default for Compression {
+	fn default() -> Self {
+		Compression::new()
+	}
+}
+
+/// Returns true for content types that are already compressed, compressing
+/// them again only wastes cpu.
+pub(crate) fn is_compressed(mime_type: &Mime) -> bool {
+	match (mime_type.type_().as_str(), mime_type.subtype().as_str()) {
+		("image", "svg") => false,
+		("image", _) | ("video", _) | ("audio", _) => true,
+		("font", "woff") | ("font", "woff2") => true,
+		("application", subtype) => {
+			match subtype {
+				"zip" | "gzip" | "x-gzip" | "x-bzip" | "x-bzip2" | "x-xz" | "x-7z-compressed"
+				| "x-rar-compressed" | "vnd.rar" | "font-woff" | "x-font-woff" | "zstd" => true,
+				_ => false,
+			}
+		}
+		_ => false,
+	}
+}
+
+impl MiddleWare<Response> for Compression {
+	fn call(&self, mut res: Response) -> MiddleWareFuture<Response> {
+		if !self.is_compressible(&res) {
+			return Box::new(future::ok(res));
+		}
+
+		res.headers_mut()
+			.append(VARY, HeaderValue::from_static("Accept-Encoding"));
+
+		let encoding = match res.request() {
+			Some(head) if head.method != Method::HEAD => {
+				head.headers
+					.get_all(ACCEPT_ENCODING)
+					.iter()
+					.filter_map(|value| value.to_str().ok())
+					.collect::<Vec<_>>()
+					.join(",")
+			}
+			_ => String::new(),
+		};
+
+		let encoding = match preferred_encoding(&encoding, &self.encodings) {
+			Some(encoding) => encoding,
+			None => return Box::new(future::ok(res)),
+		};
+
+		res.headers_mut().remove(CONTENT_LENGTH);
+		res.headers_mut().insert(
+			CONTENT_ENCODING,
+			HeaderValue::from_static(encoding.as_str()),
+		);
+
+		// ranges and strong validators refer to the uncompressed representation,
+		// which isn't the one being sent.
+		res.headers_mut().remove(ACCEPT_RANGES);
+		let weak_etag = res
+			.headers()
+			.get(ETAG)
+			.and_then(|etag| etag.to_str().ok())
+			.filter(|etag| !etag.starts_with("W/"))
+			.and_then(|etag| HeaderValue::from_str(&format!("W/{}", etag)).ok());
+		if let Some(etag) = weak_etag {
+			res.headers_mut().insert(ETAG, etag);
+		}
+
+		let body = mem::replace(&mut res.body, Body::empty());
+		res.body = Body::wrap_stream(CompressedBody {
+			body,
+			encoder: Some(Encoder::new(encoding, self.level)),
+		});
+
+		Box::new(future::ok(res))
+	}
+}
+
+enum Encoder {
+	Brotli(Box<CompressorWriter<Vec<u8>>>),
+	Gzip(GzEncoder<Vec<u8>>),
+	Deflate(ZlibEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+	fn new(encoding: Encoding, level: u32) -> Self {
+		match encoding {
+			Encoding::Brotli => {
+				Encoder::Brotli(Box::new(CompressorWriter::new(Vec::new(), 4096, level, 22)))
+			}
+			Encoding::Gzip => Encoder::Gzip(GzEncoder::new(Vec::new(), Level::new(level))),
+			Encoding::Deflate => Encoder::Deflate(ZlibEncoder::new(Vec::new(), Level::new(level))),
+		}
+	}
+
+	/// Compresses the chunk and flushes the encoder, so that the client gets
+	/// it right away, e.g for streamed or long-polling bodies.
+	fn write(&mut self, chunk: &[u8]) -> io::Result<Vec<u8>> {
+		let output = match *self {
+			Encoder::Brotli(ref mut encoder) => {
+				encoder.write_all(chunk)?;
+				encoder.flush()?;
+				encoder.get_mut()
+			}
+			Encoder::Gzip(ref mut encoder) => {
+				encoder.write_all(chunk)?;
+				encoder.flush()?;
+				encoder.get_mut()
+			}
+			Encoder::Deflate(ref mut encoder) => {
+				encoder.write_all(chunk)?;
+				encoder.flush()?;
+				encoder.get_mut()
+			}
+		};
+
+		Ok(mem::replace(output, Vec::new()))
+	}
+
+	/// Flushes the remaining compressed output.
+	fn finish(self) -> io::Result<Vec<u8>> {
+		match self {
+			Encoder::Brotli(encoder) => Ok(encoder.into_inner()),
+			Encoder::Gzip(encoder) => encoder.finish(),
+			Encoder::Deflate(encoder) => encoder.finish(),
+		}
+	}
+}
+
+/// Compresses the wrapped body as it is streamed to the client.
+struct CompressedBody {
+	body: Body,
+	encoder: Option<Encoder>,
+}
+
+impl Stream for CompressedBody {
+	type Item = Chunk;
+	type Error = Box<Error + Send + Sync>;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		loop {
+			if self.encoder.is_none() {
+				return Ok(Async::Ready(None));
+			}
+
+			let output = match try_ready!(self.body.poll()) {
+				Some(chunk) => self.encoder.as_mut().unwrap().write(&chunk)?,
+				None => self.encoder.take().unwrap().finish()?,
+			};
+
+			// an empty chunk has nothing to send.
+			if !output.is_empty() {
+				return Ok(Async::Ready(Some(output.into())));
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::Request;
+	use flate2::{read::GzDecoder, write::GzDecoder as GzWriteDecoder};
+	use futures::sync::mpsc;
+	use http::request::Builder;
+	use std::io::Read;
+
+	#[test]
+	fn it_prefers_the_encoding_with_the_highest_quality() {
+		let supported = [Encoding::Brotli, Encoding::Gzip, Encoding::Deflate];
+
+		assert_eq!(
+			preferred_encoding("gzip, deflate, br", &supported),
+			Some(Encoding::Brotli)
+		);
+		assert_eq!(
+			preferred_encoding("br;q=0.5, gzip;q=0.8", &supported),
+			Some(Encoding::Gzip)
+		);
+		assert_eq!(
+			preferred_encoding("*;q=0.1, br;q=0", &supported),
+			Some(Encoding::Gzip)
+		);
+		assert_eq!(preferred_encoding("identity", &supported), None);
+		assert_eq!(preferred_encoding("", &supported), None);
+	}
+
+	#[test]
+	fn it_compresses_the_body() {
+		let text = "Hello World ".repeat(200);
+		let req: Request = Builder::new()
+			.uri("/hello")
+			.header("accept-encoding", "gzip")
+			.body(Body::empty())
+			.unwrap()
+			.into();
+		let res = Response::for_request(&req).with_text(text.clone());
+
+		let res = Compression::new().call(res).wait().unwrap();
+		assert_eq!(res.headers()[CONTENT_ENCODING], "gzip");
+		assert_eq!(res.headers()[VARY], "Accept-Encoding");
+		assert!(!res.headers().contains_key(CONTENT_LENGTH));
+
+		let body = res.body().concat2().wait().unwrap();
+		let mut decoded = String::new();
+		GzDecoder::new(&body[..])
+			.read_to_string(&mut decoded)
+			.unwrap();
+		assert_eq!(decoded, text);
+	}
+
+	#[test]
+	fn it_drops_ranges_and_strong_validators_when_compressing() {
+		let req: Request = Builder::new()
+			.uri("/hello.txt")
+			.header("accept-encoding", "gzip")
+			.body(Body::empty())
+			.unwrap()
+			.into();
+		let respond = |etag: &'static str| {
+			let mut res = Response::for_request(&req).with_text("Hello World ".repeat(200));
+			res.headers_mut()
+				.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+			res.headers_mut()
+				.insert(ETAG, HeaderValue::from_static(etag));
+			Compression::new().call(res).wait().unwrap()
+		};
+
+		let res = respond("\"abc\"");
+		assert_eq!(res.headers()[CONTENT_ENCODING], "gzip");
+		assert!(!res.headers().contains_key(ACCEPT_RANGES));
+		assert_eq!(res.headers()[ETAG], "W/\"abc\"");
+
+		let res = respond("W/\"abc\"");
+		assert_eq!(res.headers()[ETAG], "W/\"abc\"");
+	}
+
+	#[test]
+	fn it_sends_each_chunk_before_the_body_ends() {
+		let text = "Hello World ".repeat(200);
+		let req: Request = Builder::new()
+			.uri("/events")
+			.header("accept-encoding", "gzip")
+			.body(Body::empty())
+			.unwrap()
+			.into();
+		let (sender, receiver) = mpsc::unbounded::<Chunk>();
+		let body = Body::wrap_stream(receiver.map_err(|_| io::Error::from(io::ErrorKind::Other)));
+		let res = Response::for_request(&req).with_body(body);
+
+		let res = Compression::new().call(res).wait().unwrap();
+		sender.unbounded_send(text.clone().into()).unwrap();
+
+		// the sender is still open, so the body hasn't ended yet.
+		let (chunk, _body) = res.body().into_future().wait().ok().unwrap();
+		let mut decoder = GzWriteDecoder::new(Vec::new());
+		decoder.write_all(&chunk.unwrap()).unwrap();
+		decoder.flush().unwrap();
+		assert_eq!(decoder.get_ref(), &text.into_bytes());
+	}
+
+	#[test]
+	fn it_skips_small_and_compressed_bodies() {
+		let req: Request = Builder::new()
+			.uri("/hello")
+			.header("accept-encoding", "gzip")
+			.body(Body::empty())
+			.unwrap()
+			.into();
+
+		let res = Response::for_request(&req).with_text("Hello World");
+		let res = Compression::new().call(res).wait().unwrap();
+		assert!(!res.headers().contains_key(CONTENT_ENCODING));
+
+		let mut res = Response::for_request(&req).with_body(vec![0u8; 4096]);
+		res.headers_mut()
+			.insert(CONTENT_TYPE, HeaderValue::from_static("image/png"));
+		let res = Compression::new().call(res).wait().unwrap();
+		assert!(!res.headers().contains_key(CONTENT_ENCODING));
+	}
+}
@@ -0,0 +1,540 @@
+use core::{Request, Response};
+use futures::future;
+use hyper::{
+	header::{
+		HeaderMap,
+		HeaderValue,
+		ACCESS_CONTROL_ALLOW_CREDENTIALS,
+		ACCESS_CONTROL_ALLOW_HEADERS,
+		ACCESS_CONTROL_ALLOW_METHODS,
+		ACCESS_CONTROL_ALLOW_ORIGIN,
+		ACCESS_CONTROL_EXPOSE_HEADERS,
+		ACCESS_CONTROL_MAX_AGE,
+		ACCESS_CONTROL_REQUEST_HEADERS,
+		ACCESS_CONTROL_REQUEST_METHOD,
+		ORIGIN,
+		VARY,
+	},
+	Method,
+};
+use proto::{MiddleWare, MiddleWareFuture};
+use std::sync::Arc;
+
+#[derive(Clone)]
+enum Origins {
+	Any,
+	List(Vec<String>),
+	Predicate(Arc<Fn(&str) -> bool + Send + Sync>),
+}
+
+/// A CORS middleware, implemented as both a `MiddleWare<Request>` and a
+/// `MiddleWare<Response>`.
+///
+/// As a `MiddleWare<Request>`, it answers preflight `OPTIONS` requests with an
+/// early `Err(Response)`, so they never reach your routes.
+/// As a `MiddleWare<Response>`, it adds the CORS headers to every response for
+/// an allowed origin.
+///
+/// ```rust, ignore
+/// let cors = Cors::new()
+/// 	.allow_origin("https://example.com")
+/// 	.allow_credentials(true)
+/// 	.max_age(3600);
+///
+/// let server = ArcReactor::new()
+/// 	.routes(routes)
+/// 	.before(cors.clone())
+/// 	.after(cors)
+/// 	.port(3000)
+/// 	.start();
+/// ```
+///
+/// Mount it on the `ArcReactor`, preflight requests are answered before
+/// routing, so a route doesn't need to exist for `OPTIONS`, and every
+/// response gets the CORS headers, including the ones built with
+/// `Response::new()` and the router's 404s.
+#[derive(Clone)]
+pub struct Cors {
+	origins: Origins,
+	methods: Vec<Method>,
+	headers: Option<Vec<String>>,
+	expose_headers: Vec<String>,
+	credentials: bool,
+	max_age: Option<u64>,
+}
+
+impl Cors {
+	/// Creates a `Cors` middleware that allows any origin,
+	/// the `GET`, `HEAD`, `POST`, `PUT`, `PATCH` and `DELETE` methods,
+	/// and any request headers.
+	pub fn new() -> Self {
+		Self {
+			origins: Origins::Any,
+			methods: vec![
+				Method::GET,
+				Method::HEAD,
+				Method::POST,
+				Method::PUT,
+				Method::PATCH,
+				Method::DELETE,
+			],
+			headers: None,
+			expose_headers: Vec::new(),
+			credentials: false,
+			max_age: None,
+		}
+	}
+
+	/// Allows requests from this origin, e.g `https://example.com`.
+	/// A `*` matches any subdomain, e.g `https://*.example.com` allows
+	/// `https://api.example.com` but not `https://example.com`.
+	/// Can be called multiple times to allow more origins.
+	pub fn allow_origin<T: Into<String>>(mut self, origin: T) -> Self {
+		match self.origins {
+			Origins::List(ref mut origins) => origins.push(origin.into()),
+			_ => self.origins = Origins::List(vec![origin.into()]),
+		};
+		self
+	}
+
+	/// Allows requests from any origin, this is the default.
+	pub fn allow_any_origin(mut self) -> Self {
+		self.origins = Origins::Any;
+		self
+	}
+
+	/// Allows requests from the origins for which the predicate returns true.
+	pub fn allow_origin_fn<F>(mut self, predicate: F) -> Self
+	where
+		F: Fn(&str) -> bool + Send + Sync + 'static,
+	{
+		self.origins = Origins::Predicate(Arc::new(predicate));
+		self
+	}
+
+	/// Sets the methods allowed in cross-origin requests.
+	pub fn allow_methods<I: IntoIterator<Item = Method>>(mut self, methods: I) -> Self {
+		self.methods = methods.into_iter().collect();
+		self
+	}
+
+	/// Sets the request headers allowed in cross-origin requests.
+	/// If this isn't set, any header the preflight asks for is allowed.
+	pub fn allow_headers<I, T>(mut self, headers: I) -> Self
+	where
+		I: IntoIterator<Item = T>,
+		T: Into<String>,
+	{
+		self.headers = Some(
+			headers
+				.into_iter()
+				.map(|header| header.into().to_lowercase())
+				.collect(),
+		);
+		self
+	}
+
+	/// Sets the response headers the browser exposes to the client.
+	pub fn expose_headers<I, T>(mut self, headers: I) -> Self
+	where
+		I: IntoIterator<Item = T>,
+		T: Into<String>,
+	{
+		self.expose_headers = headers.into_iter().map(Into::into).collect();
+		self
+	}
+
+	/// Allows cookies and credentials in cross-origin requests.
+	/// When allowed, the origin is echoed back instead of `*`.
+	pub fn allow_credentials(mut self, credentials: bool) -> Self {
+		self.credentials = credentials;
+		self
+	}
+
+	/// Sets how long, in seconds, browsers may cache the preflight response.
+	pub fn max_age(mut self, seconds: u64) -> Self {
+		self.max_age = Some(seconds);
+		self
+	}
+
+	/// Returns the `Access-Control-Allow-Origin` value for this origin,
+	/// or `None` if the origin isn't allowed.
+	fn allowed_origin(&self, origin: &str) -> Option<HeaderValue> {
+		let allowed = match self.origins {
+			Origins::Any if !self.credentials => return Some(HeaderValue::from_static("*")),
+			Origins::Any => true,
+			Origins::List(ref origins) => origins
+				.iter()
+				.any(|allowed| origin_matches(allowed, origin)),
+			Origins::Predicate(ref predicate) => predicate(origin),
+		};
+
+		if allowed {
+			HeaderValue::from_str(origin).ok()
+		} else {
+			None
+		}
+	}
+
+	/// Whether responses depend on the request's origin, they don't only when
+	/// any origin is allowed without credentials, as they all get `*`.
+	fn varies_by_origin(&self) -> bool {
+		match self.origins {
+			Origins::Any => self.credentials,
+			_ => true,
+		}
+	}
+
+	fn preflight(&self, req: &Request, origin: &str) -> Response {
+		let forbidden = Response::new().forbidden();
+
+		let allowed_origin = match self.allowed_origin(origin) {
+			Some(value) => value,
+			None => return forbidden,
+		};
+
+		let method = req
+			.headers()
+			.get(ACCESS_CONTROL_REQUEST_METHOD)
+			.and_then(|value| value.to_str().ok())
+			.and_then(|value| value.parse::<Method>().ok());
+
+		match method {
+			Some(ref method) if self.methods.contains(method) => {}
+			_ => return forbidden,
+		};
+
+		let requested_headers = req
+			.headers()
+			.get(ACCESS_CONTROL_REQUEST_HEADERS)
+			.and_then(|value| value.to_str().ok())
+			.map(|value| {
+				value
+					.split(',')
+					.map(|header| header.trim().to_lowercase())
+					.filter(|header| !header.is_empty())
+					.collect::<Vec<_>>()
+			})
+			.unwrap_or_default();
+
+		let allowed_headers = match self.headers {
+			Some(ref headers) => {
+				if !requested_headers.iter().all(|h| headers.contains(h)) {
+					return forbidden;
+				}
+				headers.join(", ")
+			}
+			None => requested_headers.join(", "),
+		};
+
+		let methods = self
+			.methods
+			.iter()
+			.map(Method::as_str)
+			.collect::<Vec<_>>()
+			.join(", ");
+
+		let mut res = Response::new().with_status(204);
+		{
+			let headers = res.headers_mut();
+			headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allowed_origin);
+			headers.insert(
+				ACCESS_CONTROL_ALLOW_METHODS,
+				HeaderValue::from_str(&methods).unwrap(),
+			);
+			if !allowed_headers.is_empty() {
+				if let Ok(value) = HeaderValue::from_str(&allowed_headers) {
+					headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, value);
+				}
+			}
+			if self.credentials {
+				headers.insert(
+					ACCESS_CONTROL_ALLOW_CREDENTIALS,
+					HeaderValue::from_static("true"),
+				);
+			}
+			if let Some(max_age) = self.max_age {
+				headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from(max_age));
+			}
+			headers.append(
+				VARY,
+				HeaderValue::from_static(
+					"Origin, Access-Control-Request-Method, Access-Control-Request-Headers",
+				),
+			);
+		}
+
+		res
+	}
+}
+
+/// Matches an origin against an allowed origin, which can have a `*` in place
+/// of the subdomains.
+fn origin_matches(allowed: &str, origin: &str) -> bool {
+	let mut parts = allowed.splitn(2, '*');
+	let prefix = parts.next().unwrap_or("");
+	let suffix = match parts.next() {
+		Some(suffix) => suffix,
+		None => return allowed == origin,
+	};
+
+	origin.len() > prefix.len() + suffix.len()
+		&& origin.starts_with(prefix)
+		&& origin.ends_with(suffix)
+		&& origin[prefix.len()..origin.len() - suffix.len()]
+			.bytes()
+			.all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'.')
+}
+
+/// Adds `Origin` to the `Vary` header, unless it's already listed.
+fn vary_by_origin(headers: &mut HeaderMap<HeaderValue>) {
+	let listed = headers
+		.get_all(VARY)
+		.iter()
+		.filter_map(|value| value.to_str().ok())
+		.flat_map(|value| value.split(','))
+		.any(|name| {
+			let name = name.trim();
+			name == "*" || name.eq_ignore_ascii_case("origin")
+		});
+
+	if !listed {
+		headers.append(VARY, HeaderValue::from_static("Origin"));
+	}
+}
+
+impl Default for Cors {
+	fn default() -> Self {
+		Cors::new()
+	}
+}
+
+impl MiddleWare<Request> for Cors {
+	fn call(&self, req: Request) -> MiddleWareFuture<Request> {
+		let origin = {
+			let is_preflight = req.method() == Method::OPTIONS
+				&& req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD);
+
+			match req.headers().get(ORIGIN) {
+				Some(origin) if is_preflight => origin.to_str().ok().map(String::from),
+				_ => None,
+			}
+		};
+
+		match origin {
+			// if a MiddleWare<T> returns Err(Response)
+			// that reponse is forwarded directly to the client.
+			Some(origin) => Box::new(future::err(self.preflight(&req, &origin))),
+			None => Box::new(future::ok(req)),
+		}
+	}
+}
+
+impl MiddleWare<Response> for Cors {
+	fn call(&self, mut res: Response) -> MiddleWareFuture<Response> {
+		let allowed_origin = res
+			.request()
+			.and_then(|head| head.headers.get(ORIGIN))
+			.and_then(|origin| origin.to_str().ok())
+			.and_then(|origin| self.allowed_origin(origin));
+
+		let headers = res.headers_mut();
+		// a response without CORS headers has to vary too, or a cache could
+		// serve it to an allowed origin.
+		if self.varies_by_origin() {
+			vary_by_origin(headers);
+		}
+
+		if let Some(allowed_origin) = allowed_origin {
+			headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allowed_origin);
+			if self.credentials {
+				headers.insert(
+					ACCESS_CONTROL_ALLOW_CREDENTIALS,
+					HeaderValue::from_static("true"),
+				);
+			}
+			if !self.expose_headers.is_empty() {
+				if let Ok(value) = HeaderValue::from_str(&self.expose_headers.join(", ")) {
+					headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, value);
+				}
+			}
+		}
+
+		Box::new(future::ok(res))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use contrib::FakeReactor;
+	use futures::Future;
+	use http::request::Builder;
+	use hyper::{Body, HeaderMap, StatusCode};
+	use proto::ArcHandler;
+	use routing::Router;
+
+	fn request(method: Method, headers: &[(&'static str, &'static str)]) -> Request {
+		let mut builder = Builder::new();
+		builder.method(method).uri("/hello");
+		for &(name, value) in headers {
+			builder.header(name, value);
+		}
+		builder.body(Body::empty()).unwrap().into()
+	}
+
+	#[test]
+	fn it_answers_preflight_requests() {
+		let cors = Cors::new()
+			.allow_origin("https://example.com")
+			.allow_credentials(true)
+			.max_age(600);
+
+		let req = request(
+			Method::OPTIONS,
+			&[
+				("origin", "https://example.com"),
+				("access-control-request-method", "PUT"),
+				("access-control-request-headers", "Content-Type"),
+			],
+		);
+		let res = MiddleWare::<Request>::call(&cors, req)
+			.wait()
+			.expect_err("preflight should short-circuit");
+		assert_eq!(res.status(), StatusCode::NO_CONTENT);
+		assert_eq!(
+			res.headers()[ACCESS_CONTROL_ALLOW_ORIGIN],
+			"https://example.com"
+		);
+		assert_eq!(res.headers()[ACCESS_CONTROL_ALLOW_HEADERS], "content-type");
+		assert_eq!(res.headers()[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+		assert_eq!(res.headers()[ACCESS_CONTROL_MAX_AGE], "600");
+
+		let req = request(
+			Method::OPTIONS,
+			&[
+				("origin", "https://evil.com"),
+				("access-control-request-method", "PUT"),
+			],
+		);
+		let res = MiddleWare::<Request>::call(&cors, req)
+			.wait()
+			.expect_err("preflight should short-circuit");
+		assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+		let req = request(Method::OPTIONS, &[("origin", "https://example.com")]);
+		assert!(MiddleWare::<Request>::call(&cors, req).wait().is_ok());
+	}
+
+	#[test]
+	fn it_adds_cors_headers_to_responses() {
+		let cors = Cors::new().expose_headers(vec!["X-Total-Count"]);
+
+		let req = request(Method::GET, &[("origin", "https://example.com")]);
+		let res = MiddleWare::<Response>::call(&cors, Response::for_request(&req))
+			.wait()
+			.unwrap();
+		assert_eq!(res.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+		assert_eq!(
+			res.headers()[ACCESS_CONTROL_EXPOSE_HEADERS],
+			"X-Total-Count"
+		);
+		// every origin gets `*`, so the response doesn't depend on it.
+		assert!(!res.headers().contains_key(VARY));
+
+		let cors = Cors::new().allow_origin_fn(|origin| origin.ends_with(".example.com"));
+
+		let req = request(Method::GET, &[("origin", "https://api.example.com")]);
+		let res = MiddleWare::<Response>::call(&cors, Response::for_request(&req))
+			.wait()
+			.unwrap();
+		assert_eq!(res.headers()[VARY], "Origin");
+
+		let req = request(Method::GET, &[("origin", "https://example.org")]);
+		let res = MiddleWare::<Response>::call(&cors, Response::for_request(&req))
+			.wait()
+			.unwrap();
+		assert!(!res.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+	}
+
+	#[test]
+	fn it_varies_by_origin_for_disallowed_origins() {
+		let cors = Cors::new().allow_origin("https://example.com");
+
+		for headers in &[&[("origin", "https://evil.com")][..], &[]] {
+			let req = request(Method::GET, headers);
+			let res = MiddleWare::<Response>::call(&cors, Response::for_request(&req))
+				.wait()
+				.unwrap();
+			assert!(!res.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+			assert_eq!(res.headers()[VARY], "Origin");
+		}
+
+		// a preflight response that goes through the response middlewares.
+		let req = request(
+			Method::OPTIONS,
+			&[
+				("origin", "https://example.com"),
+				("access-control-request-method", "PUT"),
+			],
+		);
+		let res = MiddleWare::<Request>::call(&cors, req).wait().unwrap_err();
+		let res = MiddleWare::<Response>::call(&cors, res).wait().unwrap();
+		assert_eq!(res.headers().get_all(VARY).iter().count(), 1);
+	}
+
+	#[test]
+	fn it_matches_subdomain_patterns() {
+		assert!(origin_matches(
+			"https://*.example.com",
+			"https://api.example.com"
+		));
+		assert!(origin_matches(
+			"https://*.example.com",
+			"https://a.b.example.com"
+		));
+		assert!(!origin_matches(
+			"https://*.example.com",
+			"https://example.com"
+		));
+		assert!(!origin_matches(
+			"https://*.example.com",
+			"http://api.example.com"
+		));
+		assert!(!origin_matches(
+			"https://*.example.com",
+			"https://evil.com/.example.com"
+		));
+		assert!(origin_matches("https://example.com", "https://example.com"));
+	}
+
+	#[test]
+	fn it_adds_cors_headers_to_responses_built_from_scratch() {
+		fn NewService(_req: Request, _res: Response) -> Response {
+			Response::new().with_status(201)
+		}
+
+		let cors = Cors::new().allow_origin("https://*.example.com");
+		let handler = ArcHandler {
+			before: None,
+			handler: Some(Box::new(Router::new().get("/new", NewService))),
+			after: Some(Box::new(cors)),
+		};
+		let mut headers = HeaderMap::new();
+		headers.insert(ORIGIN, HeaderValue::from_static("https://api.example.com"));
+
+		let reactor = FakeReactor::new(handler);
+		let res = reactor.get("/new", Some(headers.clone())).unwrap();
+		assert_eq!(res.status(), StatusCode::CREATED);
+		assert_eq!(
+			res.headers()[ACCESS_CONTROL_ALLOW_ORIGIN],
+			"https://api.example.com"
+		);
+
+		// the router's own 404.
+		let reactor = FakeReactor::new(Router::new().after(Cors::new()));
+		let res = reactor.get("/missing", Some(headers)).unwrap();
+		assert_eq!(res.status(), StatusCode::NOT_FOUND);
+		assert_eq!(res.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+	}
+}
@@ -0,0 +1,220 @@
+//! Decoders for request bodies, keyed by media type.
+//!
+//! The `BodyParser` buffers any body it has a decoder for, and
+//! `Request::body_as()` deserializes it with the decoder matching its
+//! Content-Type. JSON is always available, MessagePack and CBOR are behind
+//! the `msgpack` and `cbor` features.
+use core::Request;
+use erased_serde::{self, Deserializer};
+use futures::future;
+use proto::{MiddleWare, MiddleWareFuture};
+#[cfg(feature = "msgpack")]
+use rmp_serde;
+use serde::de::Error;
+#[cfg(feature = "cbor")]
+use serde_cbor;
+use serde_json;
+use std::{collections::HashMap, sync::Arc};
+
+/// Decodes request bodies of a particular media type.
+///
+/// Decoders go through serde, so they're implemented for self-describing
+/// formats. Register one with `Decoders::register`.
+///
+/// ```rust, ignore
+/// struct YamlDecoder;
+///
+/// impl Decoder for YamlDecoder {
+/// 	fn decode(
+/// 		&self,
+/// 		body: &[u8],
+/// 		visit: &mut FnMut(&mut Deserializer) -> Result<(), erased_serde::Error>,
+/// 	) -> Result<(), erased_serde::Error> {
+/// 		let mut de = serde_yaml::Deserializer::from_slice(body);
+/// 		visit(&mut Deserializer::erase(&mut de))
+/// 	}
+/// }
+/// ```
+pub trait Decoder: Send + Sync {
+	/// Calls `visit` with a deserializer for the body.
+	fn decode(
+		&self,
+		body: &[u8],
+		visit: &mut FnMut(&mut Deserializer) -> Result<(), erased_serde::Error>,
+	) -> Result<(), erased_serde::Error>;
+}
+
+/// Decodes `application/json`, `text/json` and `+json` bodies.
+#[derive(Clone, Copy, Debug)]
+pub struct JsonDecoder;
+
+impl Decoder for JsonDecoder {
+	fn decode(
+		&self,
+		body: &[u8],
+		visit: &mut FnMut(&mut Deserializer) -> Result<(), erased_serde::Error>,
+	) -> Result<(), erased_serde::Error> {
+		let mut de = serde_json::Deserializer::from_slice(body);
+		visit(&mut Deserializer::erase(&mut de))?;
+		// trailing characters are an error.
+		de.end().map_err(erased_serde::Error::custom)
+	}
+}
+
+/// Decodes `application/msgpack` bodies.
+#[cfg(feature = "msgpack")]
+#[derive(Clone, Copy, Debug)]
+pub struct MsgPackDecoder;
+
+#[cfg(feature = "msgpack")]
+impl Decoder for MsgPackDecoder {
+	fn decode(
+		&self,
+		body: &[u8],
+		visit: &mut FnMut(&mut Deserializer) -> Result<(), erased_serde::Error>,
+	) -> Result<(), erased_serde::Error> {
+		let mut de = rmp_serde::Deserializer::new(body);
+		visit(&mut Deserializer::erase(&mut de))
+	}
+}
+
+/// Decodes `application/cbor` bodies.
+#[cfg(feature = "cbor")]
+#[derive(Clone, Copy, Debug)]
+pub struct CborDecoder;
+
+#[cfg(feature = "cbor")]
+impl Decoder for CborDecoder {
+	fn decode(
+		&self,
+		body: &[u8],
+		visit: &mut FnMut(&mut Deserializer) -> Result<(), erased_serde::Error>,
+	) -> Result<(), erased_serde::Error> {
+		let mut de = serde_cbor::Deserializer::from_slice(body);
+		visit(&mut Deserializer::erase(&mut de))?;
+		de.end().map_err(erased_serde::Error::custom)
+	}
+}
+
+/// The registry of body decoders, keyed by media type.
+///
+/// It's a request middleware, mount it before the `BodyParser` to replace the
+/// default decoders for the requests it applies to.
+///
+/// A Content-Type with a structured syntax suffix falls back to the decoder
+/// of that suffix, e.g `application/vnd.api+json` is decoded as
+/// `application/json` unless it has a decoder of its own. Any other
+/// Content-Type that mentions json, e.g `application/x-json`, falls back to
+/// the `application/json` decoder too.
+///
+/// ```rust, ignore
+/// let decoders = Decoders::new().register("application/yaml", YamlDecoder);
+///
+/// ArcReactor::new()
+/// 	.before(mw![decoders, BodyParser])
+/// 	.routes(routes);
+/// ```
+#[derive(Clone)]
+pub struct Decoders {
+	decoders: Arc<HashMap<String, Arc<Decoder>>>,
+}
+
+impl Decoders {
+	/// The default decoders, JSON and whichever of MessagePack and CBOR are
+	/// enabled.
+	pub fn new() -> Self {
+		Decoders::default()
+	}
+
+	/// Use `decoder` for bodies of the media type, replacing the decoder that
+	/// was registered for it.
+	pub fn register<D>(mut self, mime: &str, decoder: D) -> Self
+	where
+		D: Decoder + 'static,
+	{
+		Arc::make_mut(&mut self.decoders).insert(mime.to_ascii_lowercase(), Arc::new(decoder));
+
+		self
+	}
+
+	/// Stop decoding bodies of the media type.
+	pub fn remove(mut self, mime: &str) -> Self {
+		Arc::make_mut(&mut self.decoders).remove(&mime.to_ascii_lowercase());
+
+		self
+	}
+
+	/// Returns the media type the decoder was registered for, along with the
+	/// decoder for this Content-Type.
+	pub(crate) fn find(&self, content_type: &str) -> Option<(String, Arc<Decoder>)> {
+		let essence = content_type
+			.split(';')
+			.next()
+			.unwrap_or("")
+			.trim()
+			.to_ascii_lowercase();
+		if let Some(decoder) = self.decoders.get(&essence) {
+			return Some((essence, decoder.clone()));
+		}
+
+		let fallback = match essence.rfind('+') {
+			Some(index) => format!("application/{}", &essence[index + 1..]),
+			None if essence.contains("json") => "application/json".to_owned(),
+			None => return None,
+		};
+		self.decoders
+			.get(&fallback)
+			.map(|decoder| (fallback, decoder.clone()))
+	}
+}
+
+impl Default for Decoders {
+	fn default() -> Self {
+		let mut decoders: HashMap<String, Arc<Decoder>> = HashMap::new();
+		decoders.insert("application/json".into(), Arc::new(JsonDecoder));
+		decoders.insert("text/json".into(), Arc::new(JsonDecoder));
+		#[cfg(feature = "msgpack")]
+		{
+			decoders.insert("application/msgpack".into(), Arc::new(MsgPackDecoder));
+			decoders.insert("application/x-msgpack".into(), Arc::new(MsgPackDecoder));
+		}
+		#[cfg(feature = "cbor")]
+		{
+			decoders.insert("application/cbor".into(), Arc::new(CborDecoder));
+		}
+
+		Decoders {
+			decoders: Arc::new(decoders),
+		}
+	}
+}
+
+impl MiddleWare<Request> for Decoders {
+	fn call(&self, mut req: Request) -> MiddleWareFuture<Request> {
+		req.set(self.clone());
+		Box::new(future::ok(req))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn it_finds_decoders_by_media_type() {
+		let decoders = Decoders::new();
+
+		let (mime, _) = decoders.find("Application/JSON; charset=utf-8").unwrap();
+		assert_eq!(mime, "application/json");
+		let (mime, _) = decoders.find("application/vnd.api+json").unwrap();
+		assert_eq!(mime, "application/json");
+		let (mime, _) = decoders.find("text/json").unwrap();
+		assert_eq!(mime, "text/json");
+		let (mime, _) = decoders.find("application/x-json").unwrap();
+		assert_eq!(mime, "application/json");
+		assert!(decoders.find("application/octet-stream").is_none());
+
+		let decoders = decoders.remove("application/json");
+		assert!(decoders.find("application/json").is_none());
+	}
+}
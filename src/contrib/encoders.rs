@@ -0,0 +1,256 @@
+//! Encoders for response bodies, keyed by media type.
+//!
+//! `Response::negotiate()` serializes a value with the encoder the client
+//! prefers according to its `Accept` header. JSON and plain text are always
+//! available, MessagePack and CBOR are behind the `msgpack` and `cbor`
+//! features.
+use core::{qualities, Request};
+use erased_serde::Serialize;
+use futures::future;
+use proto::{MiddleWare, MiddleWareFuture};
+#[cfg(feature = "msgpack")]
+use rmp_serde;
+#[cfg(feature = "cbor")]
+use serde_cbor;
+use serde_json::{self, Value};
+use std::{error::Error, sync::Arc};
+
+/// Encodes response bodies into a particular media type.
+///
+/// Register one with `Encoders::register`.
+///
+/// ```rust, ignore
+/// struct YamlEncoder;
+///
+/// impl Encoder for YamlEncoder {
+/// 	fn encode(&self, value: &Serialize) -> Result<Vec<u8>, Box<Error + Send + Sync>> {
+/// 		Ok(serde_yaml::to_vec(value)?)
+/// 	}
+/// }
+/// ```
+pub trait Encoder: Send + Sync {
+	fn encode(&self, value: &Serialize) -> Result<Vec<u8>, Box<Error + Send + Sync>>;
+}
+
+/// Encodes `application/json` bodies.
+#[derive(Clone, Copy, Debug)]
+pub struct JsonEncoder;
+
+impl Encoder for JsonEncoder {
+	fn encode(&self, value: &Serialize) -> Result<Vec<u8>, Box<Error + Send + Sync>> {
+		Ok(serde_json::to_vec(value)?)
+	}
+}
+
+/// Encodes `text/plain` bodies, strings are written as is and anything else
+/// as json.
+#[derive(Clone, Copy, Debug)]
+pub struct TextEncoder;
+
+impl Encoder for TextEncoder {
+	fn encode(&self, value: &Serialize) -> Result<Vec<u8>, Box<Error + Send + Sync>> {
+		match serde_json::to_value(value)? {
+			Value::String(text) => Ok(text.into_bytes()),
+			value => Ok(value.to_string().into_bytes()),
+		}
+	}
+}
+
+/// Encodes `application/msgpack` bodies, structs are written as maps.
+#[cfg(feature = "msgpack")]
+#[derive(Clone, Copy, Debug)]
+pub struct MsgPackEncoder;
+
+#[cfg(feature = "msgpack")]
+impl Encoder for MsgPackEncoder {
+	fn encode(&self, value: &Serialize) -> Result<Vec<u8>, Box<Error + Send + Sync>> {
+		Ok(rmp_serde::to_vec_named(value)?)
+	}
+}
+
+/// Encodes `application/cbor` bodies.
+#[cfg(feature = "cbor")]
+#[derive(Clone, Copy, Debug)]
+pub struct CborEncoder;
+
+#[cfg(feature = "cbor")]
+impl Encoder for CborEncoder {
+	fn encode(&self, value: &Serialize) -> Result<Vec<u8>, Box<Error + Send + Sync>> {
+		// unlike the other serializers, serde_cbor needs a sized value.
+		Ok(serde_cbor::to_vec(&value)?)
+	}
+}
+
+/// The registry of response encoders, in order of preference.
+///
+/// It's a request middleware, mount it to replace the default encoders used
+/// by `Response::negotiate()` for the requests it applies to.
+///
+/// ```rust, ignore
+/// let encoders = Encoders::new().register("application/yaml", YamlEncoder);
+///
+/// ArcReactor::new()
+/// 	.before(mw![encoders, BodyParser])
+/// 	.routes(routes);
+/// ```
+#[derive(Clone)]
+pub struct Encoders {
+	encoders: Arc<Vec<(String, Arc<Encoder>)>>,
+}
+
+impl Encoders {
+	/// The default encoders, JSON, whichever of MessagePack and CBOR are
+	/// enabled, and plain text.
+	pub fn new() -> Self {
+		Encoders::default()
+	}
+
+	/// Use `encoder` for the media type. It replaces the encoder that was
+	/// registered for it, or is the least preferred one.
+	pub fn register<E>(mut self, mime: &str, encoder: E) -> Self
+	where
+		E: Encoder + 'static,
+	{
+		let mime = mime.to_ascii_lowercase();
+		let encoder: Arc<Encoder> = Arc::new(encoder);
+		{
+			let encoders = Arc::make_mut(&mut self.encoders);
+			match encoders.iter().position(|&(ref other, _)| *other == mime) {
+				Some(index) => encoders[index].1 = encoder,
+				None => encoders.push((mime, encoder)),
+			}
+		}
+
+		self
+	}
+
+	/// Stop encoding to the media type.
+	pub fn remove(mut self, mime: &str) -> Self {
+		let mime = mime.to_ascii_lowercase();
+		Arc::make_mut(&mut self.encoders).retain(|&(ref other, _)| *other != mime);
+
+		self
+	}
+
+	/// Picks the encoder the client prefers according to the q-values of the
+	/// `Accept` header, ties are broken by the order of the encoders.
+	/// Without an `Accept` header, or with an empty one, the first encoder is
+	/// used.
+	pub(crate) fn negotiate(&self, accept: Option<&str>) -> Option<(String, Arc<Encoder>)> {
+		let ranges = accept.map(qualities).unwrap_or_default();
+		if ranges.is_empty() {
+			return self.encoders.first().cloned();
+		}
+
+		let mut preferred: Option<(&(String, Arc<Encoder>), f32)> = None;
+		for encoder in self.encoders.iter() {
+			let mime = &encoder.0;
+			let wildcard = format!("{}/*", mime.split('/').next().unwrap_or(""));
+
+			// the most specific range that matches applies.
+			let quality = [mime.as_str(), wildcard.as_str(), "*/*"]
+				.iter()
+				.filter_map(|candidate| {
+					ranges
+						.iter()
+						.find(|&&(ref range, _)| range == candidate)
+						.map(|&(_, quality)| quality)
+				})
+				.next()
+				.unwrap_or(0.0);
+
+			if quality <= 0.0 {
+				continue;
+			}
+
+			match preferred {
+				Some((_, best)) if best >= quality => {}
+				_ => preferred = Some((encoder, quality)),
+			}
+		}
+
+		preferred.map(|(encoder, _)| encoder.clone())
+	}
+}
+
+impl Default for Encoders {
+	fn default() -> Self {
+		let mut encoders: Vec<(String, Arc<Encoder>)> = Vec::new();
+		encoders.push(("application/json".into(), Arc::new(JsonEncoder)));
+		#[cfg(feature = "msgpack")]
+		{
+			encoders.push(("application/msgpack".into(), Arc::new(MsgPackEncoder)));
+		}
+		#[cfg(feature = "cbor")]
+		{
+			encoders.push(("application/cbor".into(), Arc::new(CborEncoder)));
+		}
+		encoders.push(("text/plain".into(), Arc::new(TextEncoder)));
+
+		Encoders {
+			encoders: Arc::new(encoders),
+		}
+	}
+}
+
+impl MiddleWare<Request> for Encoders {
+	fn call(&self, mut req: Request) -> MiddleWareFuture<Request> {
+		req.set(self.clone());
+		Box::new(future::ok(req))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::Response;
+	use http::request::Builder;
+	use hyper::{header::CONTENT_TYPE, Body};
+
+	fn negotiate(accept: Option<&str>) -> Option<String> {
+		Encoders::new().negotiate(accept).map(|(mime, _)| mime)
+	}
+
+	#[test]
+	fn it_negotiates_with_q_values() {
+		assert_eq!(negotiate(None).unwrap(), "application/json");
+		assert_eq!(negotiate(Some("*/*")).unwrap(), "application/json");
+		assert_eq!(
+			negotiate(Some("text/plain, application/json;q=0.5")).unwrap(),
+			"text/plain"
+		);
+		assert_eq!(
+			negotiate(Some("text/*;q=0.9, application/json;q=0.1")).unwrap(),
+			"text/plain"
+		);
+		assert_eq!(
+			negotiate(Some("*/*;q=0.1, application/*;q=0")).unwrap(),
+			"text/plain"
+		);
+		assert!(negotiate(Some("image/png")).is_none());
+		assert_eq!(negotiate(Some("")).unwrap(), "application/json");
+		assert_eq!(negotiate(Some(" ")).unwrap(), "application/json");
+	}
+
+	#[test]
+	fn it_responds_with_the_negotiated_encoding() {
+		let req: Request = Builder::new()
+			.uri("/users")
+			.header("accept", "text/plain")
+			.body(Body::empty())
+			.unwrap()
+			.into();
+		let res = Response::negotiate(&req, &"seun");
+		assert_eq!(res.status().as_u16(), 200);
+		assert_eq!(res.headers()[CONTENT_TYPE], "text/plain; charset=utf-8");
+
+		let req: Request = Builder::new()
+			.uri("/users")
+			.header("accept", "image/png")
+			.body(Body::empty())
+			.unwrap()
+			.into();
+		let res = Response::negotiate(&req, &"seun");
+		assert_eq!(res.status().as_u16(), 406);
+	}
+}
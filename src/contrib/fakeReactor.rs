@@ -43,6 +43,16 @@ impl FakeReactor {
 		self.build(Method::GET, route, None::<u8>, headers)
 	}
 
+	/// Send a HEAD request to the `FakeReactor`.
+	pub fn head(&self, route: &str, headers: Option<HeaderMap>) -> Result<Response, Response> {
+		self.build(Method::HEAD, route, None::<u8>, headers)
+	}
+
+	/// Send an OPTIONS request to the `FakeReactor`.
+	pub fn options(&self, route: &str, headers: Option<HeaderMap>) -> Result<Response, Response> {
+		self.build(Method::OPTIONS, route, None::<u8>, headers)
+	}
+
 	/// Send a PUT request to the `FakeReactor`.
 	pub fn put<T>(
 		&self,
@@ -111,8 +121,9 @@ impl FakeReactor {
 			.unwrap();
 		*request.headers_mut() = headers;
 		let req: Request = request.into();
+		let res = Response::for_request(&req);
 
-		return reactor.block_on(self.service.call(req, Response::new()));
+		return reactor.block_on(self.service.call(req, res));
 	}
 }
 
@@ -121,6 +132,10 @@ mod tests {
 	use super::*;
 	use core::{Request, Response};
 	use futures::{future, Future, Stream};
+	use hyper::{
+		header::{ALLOW, CONTENT_LENGTH},
+		StatusCode,
+	};
 	use proto::FutureResponse;
 	use routing::*;
 
@@ -143,4 +158,44 @@ mod tests {
 		let body = result.body().concat2().wait().expect("Body should be Ok");
 		assert_eq!(&body[..], b"Hello World");
 	}
+
+	#[test]
+	fn it_returns_method_not_allowed_with_the_allowed_methods() {
+		let routes = Router::new()
+			.get("/hello", AsyncService)
+			.put("/hello", AsyncService);
+
+		let fakereactor = FakeReactor::new(routes);
+
+		let result = fakereactor
+			.post("/hello", None::<u8>, None)
+			.expect("Should return ok");
+		assert_eq!(result.status(), StatusCode::METHOD_NOT_ALLOWED);
+		assert_eq!(result.headers()[ALLOW], "GET, HEAD, OPTIONS, PUT");
+
+		let result = fakereactor
+			.post("/world", None::<u8>, None)
+			.expect("Should return ok");
+		assert_eq!(result.status(), StatusCode::NOT_FOUND);
+	}
+
+	#[test]
+	fn it_answers_head_and_options_requests() {
+		let routes = Router::new().get("/hello", AsyncService);
+
+		let fakereactor = FakeReactor::new(routes);
+
+		let result = fakereactor.get("/hello", None).expect("Should return ok");
+		let get = result.body().concat2().wait().expect("Body should be Ok");
+
+		let result = fakereactor.head("/hello", None).expect("Should return ok");
+		assert_eq!(result.status(), StatusCode::OK);
+		assert_eq!(result.headers()[CONTENT_LENGTH], get.len().to_string());
+		let body = result.body().concat2().wait().expect("Body should be Ok");
+		assert!(body.is_empty());
+
+		let result = fakereactor.options("/hello", None).expect("Should return ok");
+		assert_eq!(result.status(), StatusCode::OK);
+		assert_eq!(result.headers()[ALLOW], "GET, HEAD, OPTIONS");
+	}
 }
@@ -1,10 +1,20 @@
 //! Utilities that make working with arc reactor easier.
 mod bodyParser;
+mod compression;
+mod cors;
+mod decoders;
+mod encoders;
 mod fakeReactor;
-#[cfg(feature = "unstable")]
 mod multipart;
 mod staticFileServer;
 
-#[cfg(feature = "unstable")]
-pub use self::multipart::*;
-pub use self::{bodyParser::*, fakeReactor::*, staticFileServer::*};
+pub use self::{
+	bodyParser::*,
+	compression::*,
+	cors::*,
+	decoders::*,
+	encoders::*,
+	fakeReactor::*,
+	multipart::*,
+	staticFileServer::*,
+};
@@ -0,0 +1,111 @@
+use core::{from_pairs, DeError};
+use mime::Mime;
+use serde::de::DeserializeOwned;
+use std::{collections::HashMap, path::PathBuf};
+
+/// A file uploaded through the `MultiPart` middleware.
+#[derive(Clone, Debug)]
+pub struct UploadedFile {
+	/// The filename supplied by the client, don't use it as a path.
+	pub filename: String,
+	/// Where the file was saved, under a unique name in the `MultiPart` dir.
+	pub path: PathBuf,
+	/// The Content-Type supplied by the client.
+	pub content_type: Option<Mime>,
+	/// The size of the file in bytes.
+	pub size: u64,
+	/// The hex encoded SHA-256 of the file, if `MultiPart::hash` was enabled.
+	pub hash: Option<String>,
+}
+
+/// The fields of a multipart form, as parsed by the `MultiPart` middleware.
+///
+/// Every field keeps all the values it was sent with, in order.
+#[derive(Clone, Debug, Default)]
+pub struct Form {
+	fields: HashMap<String, Vec<String>>,
+	files: HashMap<String, Vec<UploadedFile>>,
+}
+
+impl Form {
+	pub(crate) fn push_field(&mut self, name: String, value: String) {
+		self.fields.entry(name).or_insert_with(Vec::new).push(value);
+	}
+
+	pub(crate) fn push_file(&mut self, name: String, file: UploadedFile) {
+		self.files.entry(name).or_insert_with(Vec::new).push(file);
+	}
+
+	pub(crate) fn field_count(&self) -> u64 {
+		self.fields.values().map(|values| values.len() as u64).sum()
+	}
+
+	pub(crate) fn file_count(&self) -> u64 {
+		self.files.values().map(|files| files.len() as u64).sum()
+	}
+
+	/// Returns the first value of the text field.
+	pub fn get(&self, name: &str) -> Option<&str> {
+		self.get_all(name).first().map(String::as_str)
+	}
+
+	/// Returns every value of the text field.
+	pub fn get_all(&self, name: &str) -> &[String] {
+		self.fields.get(name).map_or(&[][..], Vec::as_slice)
+	}
+
+	/// Returns the first file uploaded for the field.
+	pub fn file(&self, name: &str) -> Option<&UploadedFile> {
+		self.files(name).first()
+	}
+
+	/// Returns every file uploaded for the field.
+	pub fn files(&self, name: &str) -> &[UploadedFile] {
+		self.files.get(name).map_or(&[][..], Vec::as_slice)
+	}
+
+	/// All the text fields.
+	pub fn fields(&self) -> &HashMap<String, Vec<String>> {
+		&self.fields
+	}
+
+	/// All the uploaded files.
+	pub fn all_files(&self) -> &HashMap<String, Vec<UploadedFile>> {
+		&self.files
+	}
+
+	/// Deserializes the text fields into `T`.
+	///
+	/// Values are parsed into whatever type `T` asks for, and a field sent more
+	/// than once can only be deserialized into a sequence.
+	pub fn fields_as<T: DeserializeOwned>(&self) -> Result<T, DeError> {
+		from_pairs(
+			self.fields
+				.iter()
+				.map(|(name, values)| (name.as_str(), values.iter().map(String::as_str).collect())),
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn it_keeps_every_value() {
+		let mut form = Form::default();
+		form.push_field("title".into(), "hello".into());
+		form.push_field("tags".into(), "rust".into());
+		form.push_field("tags".into(), "web".into());
+
+		assert_eq!(form.get("tags"), Some("rust"));
+		assert_eq!(form.get_all("tags").len(), 2);
+		assert!(form.get("missing").is_none());
+
+		let fields: HashMap<String, Vec<String>> = form.fields_as().unwrap();
+		assert_eq!(fields["title"], vec!["hello"]);
+		assert_eq!(fields["tags"], vec!["rust", "web"]);
+
+		assert!(form.fields_as::<HashMap<String, String>>().is_err());
+	}
+}
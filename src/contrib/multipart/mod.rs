@@ -1,25 +1,61 @@
+mod form;
 mod parser;
-use core::Request;
-use futures::{
-	future,
-	prelude::{async_block, await},
-};
+use core::{Request, Response};
+use futures::{future, prelude::*};
 use header::{CONTENT_LENGTH, CONTENT_TYPE};
-use hyperx::header::{ContentType, Header};
-use mime::Mime;
+use mime::{self, Mime};
 use proto::{MiddleWare, MiddleWareFuture};
-use std::{collections::HashMap, path::PathBuf};
-/// A Multipart request parser.
-/// This is only avaible behind the `unstable` features flag.
+use std::{
+	fs,
+	path::PathBuf,
+	sync::{Arc, Mutex},
+};
+
+pub use self::{
+	form::*,
+	parser::{Limit, MultiPartError, MultiPartStream, Part, SavedFile},
+};
+
+/// A Multipart request parser, implemented as a `MiddleWare<Request>`.
 ///
+/// Text fields are buffered, files are saved to `dir` under a unique name and
+/// described by an `UploadedFile`, the parsed `Form` is available through
+/// `Request::form()`.
+/// Use `MultiPartStream` directly to decide per field what to do with it.
+///
+/// The limits are enforced as the body is read, so they hold for chunked
+/// uploads too. Going over one fails the request with a `413 Payload Too
+/// Large` and removes the files saved so far.
+///
+/// ```rust, ignore
+/// let routes = Router::new().post(
+/// 	"/upload",
+/// 	Route::new(UploadService).before(
+/// 		MultiPart::new(PathBuf::from("uploads"), None, Some(10 * 1024 * 1024))
+/// 			.max_files(4)
+/// 			.max_file_size(5 * 1024 * 1024),
+/// 	),
+/// );
+///
+/// fn UploadService(req: Request, res: Response) -> FutureResponse {
+/// 	let form = req.form()?;
+/// 	let avatar = form.file("avatar").unwrap();
+/// 	...
+/// }
+/// ```
 #[derive(Clone)]
 pub struct MultiPart {
 	/// list of mimes you want to accept,
 	pub mimes: Option<Vec<Mime>>,
-	/// maximum upload size for files.
+	/// maximum size of the whole request body.
 	pub size_limit: Option<u64>,
 	/// directory to put the uploaded files.
 	pub dir: PathBuf,
+	hash: bool,
+	max_file_size: Option<u64>,
+	max_files: Option<u64>,
+	max_fields: Option<u64>,
+	max_field_size: Option<u64>,
 }
 
 impl MultiPart {
@@ -28,77 +64,360 @@ impl MultiPart {
 			mimes,
 			dir,
 			size_limit,
+			hash: false,
+			max_file_size: None,
+			max_files: None,
+			max_fields: None,
+			max_field_size: None,
 		}
 	}
+
+	/// The maximum size of a single file, in bytes.
+	pub fn max_file_size(mut self, bytes: u64) -> Self {
+		self.max_file_size = Some(bytes);
+		self
+	}
+
+	/// The maximum number of files.
+	pub fn max_files(mut self, files: u64) -> Self {
+		self.max_files = Some(files);
+		self
+	}
+
+	/// The maximum number of text fields.
+	pub fn max_fields(mut self, fields: u64) -> Self {
+		self.max_fields = Some(fields);
+		self
+	}
+
+	/// The maximum size of a single text field, in bytes.
+	/// Text fields are buffered in memory, so setting this is recommended.
+	pub fn max_field_size(mut self, bytes: u64) -> Self {
+		self.max_field_size = Some(bytes);
+		self
+	}
+
+	/// Compute the SHA-256 of every uploaded file, it's available as
+	/// `UploadedFile::hash`. Defaults to false.
+	pub fn hash(mut self, hash: bool) -> Self {
+		self.hash = hash;
+		self
+	}
 }
 
-pub(crate) struct MultiPartMap(pub(crate) HashMap<String, String>);
+/// The files saved so far, they're removed when dropped unless they're kept,
+/// e.g if the request fails or the client goes away mid-upload.
+#[derive(Default)]
+struct SavedFiles(Vec<PathBuf>);
+
+impl SavedFiles {
+	fn keep(&mut self) {
+		self.0.clear();
+	}
+}
+
+impl Drop for SavedFiles {
+	fn drop(&mut self) {
+		for path in self.0.drain(..) {
+			let _ = fs::remove_file(path);
+		}
+	}
+}
+
+type FormFuture = Box<Future<Item = Form, Error = Response> + Send>;
+
+type SaveFuture = Box<Future<Item = SavedFile, Error = MultiPartError> + Send>;
+
+fn error_response(err: MultiPartError) -> Response {
+	error!("[MultiPartParser][Error] {}", err);
+	match err {
+		MultiPartError::Io(_) | MultiPartError::Hyper(_) => (500, "internal server error").into(),
+		MultiPartError::LimitExceeded(limit) => {
+			let json = json!({
+				"error": limit.to_string(),
+				"limit": limit.name(),
+				"max": limit.max(),
+			});
+			(413, json).into()
+		}
+		err => (400, err.to_string()).into(),
+	}
+}
 
 impl MiddleWare<Request> for MultiPart {
 	fn call(&self, mut req: Request) -> MiddleWareFuture<Request> {
-		let (dir, mimes) = (self.dir.clone(), self.mimes.clone());
-
-		if let (Some(len), Some(limit)) =
-			{ (req.headers().get(CONTENT_LENGTH).clone(), self.size_limit) }
-		{
-			let len = len
-				.to_str()
-				.map_err(|_| ())
-				.and_then(|len| len.parse::<u64>().map_err(|_| ()));
-			match len {
-				Ok(len) => {
-					if len > limit {
-						return Box::new(future::err(
-							(400, format!("File upload limit {} Exceeded!", limit)).into(),
-						));
-					}
-				}
-				Err(_) => {
-					return Box::new(future::err((400, "Invalid Content Length").into()));
+		let multipart = self.clone();
+
+		// a Content-Length that's already over the limit can be rejected without
+		// reading the body, anything else is checked while streaming.
+		let len = req
+			.headers()
+			.get(CONTENT_LENGTH)
+			.and_then(|len| len.to_str().ok())
+			.and_then(|len| len.parse::<u64>().ok());
+		if let (Some(len), Some(limit)) = (len, self.size_limit) {
+			if len > limit {
+				let err = MultiPartError::LimitExceeded(Limit::Size(limit));
+				return Box::new(future::err(error_response(err)));
+			}
+		}
+
+		let boundary = {
+			let content_type = req
+				.headers()
+				.get(CONTENT_TYPE)
+				.and_then(|value| value.to_str().ok())
+				.and_then(|value| value.parse::<Mime>().ok());
+
+			match content_type
+				.as_ref()
+				.and_then(|content_type| content_type.get_param(mime::BOUNDARY))
+			{
+				Some(boundary) => boundary.as_str().to_owned(),
+				None => {
+					return Box::new(future::err(
+						(400, "Unspecified Boundary for Multipart").into(),
+					))
 				}
-			};
+			}
+		};
+
+		let mut stream = MultiPartStream::new(req.body(), boundary);
+		if let Some(limit) = self.size_limit {
+			stream = stream.limit(limit);
 		}
 
-		let content_type = req.headers().get(CONTENT_TYPE).and_then(|v| {
-			let hv = v.clone();
-			hv.to_str().ok().and_then(|v| Some(v.to_string()))
-		});
+		// the files saved so far, removed unless the form reaches the request.
+		let saved = Arc::new(Mutex::new(SavedFiles::default()));
+		let kept = saved.clone();
 
-		let future = async_block! {
-			let body = { req.body() };
+		let future = stream
+			.map_err(error_response)
+			.fold(Form::default(), move |mut form, part| -> FormFuture {
+				let name = part.name().to_owned();
 
-			let boundary = {
-				let content_type = match content_type {
-					None => return Err((400, "Invalid Content Length").into()),
-					Some(c) => ContentType::parse_header(&c.into()).ok()
-				};
+				let filename = match part.filename() {
+					Some(filename) => filename.to_owned(),
+					None => {
+						if let Some(max) = multipart.max_fields {
+							if form.field_count() >= max {
+								let err = MultiPartError::LimitExceeded(Limit::Fields(max));
+								return Box::new(future::err(error_response(err)));
+							}
+						}
+						let part = match multipart.max_field_size {
+							Some(max) => part.limit(max),
+							None => part,
+						};
 
+						let future = part.buffer().map_err(error_response).map(move |value| {
+							form.push_field(name, String::from_utf8_lossy(&value).into_owned());
+							form
+						});
+						return Box::new(future);
+					}
+				};
 
-				match content_type
-					.and_then(|disp| disp.get_param("boundary").and_then(|val| Some(String::from(val.as_str()))))
-				{
-					Some(b) => b,
-					None => return Err((400, "Unspecified Boundary for Multipart").into()),
+				if let Some(max) = multipart.max_files {
+					if form.file_count() >= max {
+						let err = MultiPartError::LimitExceeded(Limit::Files(max));
+						return Box::new(future::err(error_response(err)));
+					}
 				}
-			};
-
-			match await!(parser::parse(body, boundary, dir, mimes)) {
-				Ok(map) => {
-					req.set(MultiPartMap(map));
-				},
-				Err(err) => {
-					println!("[MultiPartParser][Error] {:?}", err);
-					use self::parser::ParseError::*;
-					match err {
-						InvalidMime => return Err((400, "Invalid Content-Type").into()),
-						_ => return Err((500, "internal server error").into()),
-					};
+
+				if let Some(ref mimes) = multipart.mimes {
+					let allowed = part
+						.content_type()
+						.map_or(false, |content_type| mimes.contains(content_type));
+					if !allowed {
+						error!("[MultiPartParser] Unsupported Mime!");
+						return Box::new(future::err((400, "Invalid Content-Type").into()));
+					}
 				}
-			};
 
-			Ok(req)
-		};
+				let content_type = part.content_type().cloned();
+				let part = match multipart.max_file_size {
+					Some(max) => part.limit(max),
+					None => part,
+				};
+				let future: SaveFuture = if multipart.hash {
+					Box::new(part.save_hashed(multipart.dir.clone()))
+				} else {
+					Box::new(part.save(multipart.dir.clone()))
+				};
+
+				let saved = saved.clone();
+				let future = future.map_err(error_response).map(move |file| {
+					saved.lock().unwrap().0.push(file.path.clone());
+					form.push_file(
+						name,
+						UploadedFile {
+							filename,
+							path: file.path,
+							content_type,
+							size: file.size,
+							hash: file.hash,
+						},
+					);
+					form
+				});
+				Box::new(future)
+			})
+			.map(move |form| {
+				kept.lock().unwrap().keep();
+				req.set(form);
+				req
+			});
 
 		Box::new(future)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::stream;
+	use http::request::Builder;
+	use hyper::{self, Body};
+	use futures::sync::mpsc;
+	use hyper::Chunk;
+	use std::{
+		env,
+		fs,
+		io,
+		time::{Duration, Instant},
+	};
+	use tokio::{runtime::Runtime, timer::Delay};
+
+	#[test]
+	fn it_parses_the_form_on_stable() {
+		let dir = env::temp_dir().join(format!("arc-reactor-multipart-{}", ::std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+
+		let body = "--xyz\r\n\
+		            Content-Disposition: form-data; name=\"title\"\r\n\
+		            \r\n\
+		            hello\r\n\
+		            --xyz\r\n\
+		            Content-Disposition: form-data; name=\"file\"; filename=\"hello.txt\"\r\n\
+		            Content-Type: text/plain\r\n\
+		            \r\n\
+		            hello world\r\n\
+		            --xyz--\r\n";
+		let req: Request = Builder::new()
+			.method("POST")
+			.uri("/upload")
+			.header("content-type", "multipart/form-data; boundary=xyz")
+			.body(Body::from(body))
+			.unwrap()
+			.into();
+
+		let multipart = MultiPart::new(dir.clone(), None, None).hash(true);
+		let req = Runtime::new()
+			.unwrap()
+			.block_on(multipart.call(req))
+			.unwrap();
+		let form = req.form().unwrap();
+
+		assert_eq!(form.get("title"), Some("hello"));
+		let file = form.file("file").unwrap();
+		assert_eq!(file.filename, "hello.txt");
+		assert_eq!(file.size, 11);
+		assert_eq!(file.content_type, Some(mime::TEXT_PLAIN));
+		assert_eq!(
+			file.hash.as_ref().map(String::as_str),
+			Some("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9")
+		);
+		assert_eq!(fs::read_to_string(&file.path).unwrap(), "hello world");
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn it_enforces_limits_while_streaming() {
+		let dir = env::temp_dir().join(format!(
+			"arc-reactor-multipart-limits-{}",
+			::std::process::id()
+		));
+		fs::create_dir_all(&dir).unwrap();
+
+		let body = "--xyz\r\n\
+		            Content-Disposition: form-data; name=\"a\"; filename=\"a.txt\"\r\n\
+		            \r\n\
+		            small\r\n\
+		            --xyz\r\n\
+		            Content-Disposition: form-data; name=\"b\"; filename=\"b.txt\"\r\n\
+		            \r\n\
+		            much too large\r\n\
+		            --xyz--\r\n";
+		let chunks = body
+			.as_bytes()
+			.chunks(8)
+			.map(|chunk| chunk.to_vec())
+			.collect::<Vec<_>>();
+		// no Content-Length, the body is chunked.
+		let body = Body::wrap_stream(stream::iter_ok::<_, hyper::Error>(chunks));
+		let req: Request = Builder::new()
+			.method("POST")
+			.uri("/upload")
+			.header("content-type", "multipart/form-data; boundary=xyz")
+			.body(body)
+			.unwrap()
+			.into();
+
+		let multipart = MultiPart::new(dir.clone(), None, None).max_file_size(8);
+		let res = match Runtime::new().unwrap().block_on(multipart.call(req)) {
+			Ok(_) => panic!("the upload should have been rejected"),
+			Err(res) => res,
+		};
+
+		assert_eq!(res.status().as_u16(), 413);
+		assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn it_removes_saved_files_when_the_upload_is_dropped() {
+		let dir = env::temp_dir().join(format!(
+			"arc-reactor-multipart-dropped-{}",
+			::std::process::id()
+		));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+
+		// the first file is complete, the client is still sending the second.
+		let (sender, receiver) = mpsc::unbounded::<Chunk>();
+		let head = "--xyz\r\n\
+		            Content-Disposition: form-data; name=\"a\"; filename=\"a.txt\"\r\n\
+		            \r\n\
+		            first\r\n\
+		            --xyz\r\n\
+		            Content-Disposition: form-data; name=\"b\"; filename=\"b.txt\"\r\n\
+		            \r\n";
+		sender.unbounded_send(head.into()).unwrap();
+		sender.unbounded_send("x".repeat(64).into()).unwrap();
+		let body = Body::wrap_stream(receiver.map_err(|_| io::Error::from(io::ErrorKind::Other)));
+		let req: Request = Builder::new()
+			.method("POST")
+			.uri("/upload")
+			.header("content-type", "multipart/form-data; boundary=xyz")
+			.body(body)
+			.unwrap()
+			.into();
+
+		let multipart = MultiPart::new(dir.clone(), None, None);
+		let mut runtime = Runtime::new().unwrap();
+		let timeout = Delay::new(Instant::now() + Duration::from_millis(200));
+		let upload = runtime
+			.block_on(multipart.call(req).select2(timeout))
+			.ok()
+			.unwrap();
+
+		assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+		drop(upload);
+		assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
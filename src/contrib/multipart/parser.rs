@@ -1,193 +1,811 @@
-use bytes::Bytes;
-use core::file;
-use futures::prelude::{await, async};
-use hyper::{self, Body};
-use hyperx::header::{ContentDisposition, DispositionParam, DispositionType, Header};
-use mime::Mime;
-use regex::{
+//! An incremental multipart/form-data parser.
+//!
+//! The body is scanned for the boundary as it arrives, so a boundary that
+//! straddles two chunks is still found, and at most a boundary's worth of
+//! bytes is held back between chunks.
+use bytes::{Bytes, BytesMut};
+use futures::prelude::*;
+use hyper::{
 	self,
-	bytes::{Regex, RegexBuilder},
+	header::{HeaderMap, HeaderName, HeaderValue, CONTENT_DISPOSITION, CONTENT_TYPE},
+	Body,
+};
+use hyperx::header::{ContentDisposition, DispositionParam, Header};
+use mime::Mime;
+use sha2::{Digest, Sha256};
+use std::{
+	collections::hash_map::RandomState,
+	error,
+	fmt,
+	fs,
+	hash::{BuildHasher, Hasher},
+	io,
+	path::{Path, PathBuf},
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+		Mutex,
+	},
+	time::{SystemTime, UNIX_EPOCH},
 };
-use std::{collections::HashMap, io, path::PathBuf};
-use tokio::fs::File;
+use tokio::{fs::OpenOptions, io::write_all};
+
+/// The headers of a part can't be larger than this.
+const MAX_HEADERS_SIZE: usize = 8 * 1024;
+
+/// Mixed into the random part of every saved file's name.
+static UPLOADS: AtomicUsize = AtomicUsize::new(0);
 
 #[derive(Debug)]
-enum DecodeState {
-	Boundary,
-	Header,
-	Read(CharSet),
-}
-#[derive(Clone, Debug)]
-enum CharSet {
-	Utf8(String),
-	Binary(String, String),
-	None,
-}
-#[derive(Debug)]
-pub enum ParseError {
+pub enum MultiPartError {
 	Io(io::Error),
-	Regex(regex::Error),
 	Hyper(hyper::Error),
-	InvalidMime,
+	/// The body isn't valid multipart/form-data.
+	Malformed(&'static str),
+	/// The body ended before the closing boundary.
+	UnexpectedEof,
+	/// The body, a part, or the number of parts went over a limit.
+	LimitExceeded(Limit),
+}
+
+/// The limits that can be put on a multipart body.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Limit {
+	/// The maximum size of the whole body, in bytes.
+	Size(u64),
+	/// The maximum size of a file, in bytes.
+	FileSize(u64),
+	/// The maximum number of files.
+	Files(u64),
+	/// The maximum number of text fields.
+	Fields(u64),
+	/// The maximum size of a text field, in bytes.
+	FieldSize(u64),
 }
 
-impl From<hyper::Error> for ParseError {
-	fn from(err: hyper::Error) -> ParseError {
-		ParseError::Hyper(err)
+impl Limit {
+	/// A short name for the limit, e.g `file_size`.
+	pub fn name(&self) -> &'static str {
+		match *self {
+			Limit::Size(_) => "size",
+			Limit::FileSize(_) => "file_size",
+			Limit::Files(_) => "files",
+			Limit::Fields(_) => "fields",
+			Limit::FieldSize(_) => "field_size",
+		}
+	}
+
+	/// The value of the limit.
+	pub fn max(&self) -> u64 {
+		match *self {
+			Limit::Size(max)
+			| Limit::FileSize(max)
+			| Limit::Files(max)
+			| Limit::Fields(max)
+			| Limit::FieldSize(max) => max,
+		}
 	}
 }
 
-#[async]
-pub fn parse(
-	body: Body,
-	boundary: String,
-	dir: PathBuf,
-	mimes: Option<Vec<Mime>>,
-) -> Result<HashMap<String, String>, ParseError> {
-	let boundary = format!("--{}", boundary);
-	let re = format!("{}*", boundary);
-	let mut boundary_regex = RegexBuilder::new(&re);
-	let boundary_regex = match boundary_regex.size_limit(5000).build() {
-		Ok(regex) => regex,
-		Err(err) => {
-			error!("[MultiPart Parser] Regex Error: {}", err);
-			return Err(ParseError::Regex(err));
+impl fmt::Display for Limit {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Limit::Size(max) => write!(f, "Upload is larger than {} bytes", max),
+			Limit::FileSize(max) => write!(f, "File is larger than {} bytes", max),
+			Limit::Files(max) => write!(f, "Upload has more than {} files", max),
+			Limit::Fields(max) => write!(f, "Upload has more than {} fields", max),
+			Limit::FieldSize(max) => write!(f, "Field is larger than {} bytes", max),
 		}
-	};
-	let crlf_regex = Regex::new(r"\r\n").unwrap();
-	let mut map = HashMap::new();
-	let mut file: Option<File> = None;
-	let mut state = DecodeState::Boundary;
-
-	#[async]
-	for chunk in body {
-		let chunk = Bytes::from(chunk);
-		let matches = boundary_regex
-			.split(&chunk)
-			.map(Bytes::from)
-			.collect::<Vec<_>>();
+	}
+}
 
-		// invalid UTF8 bytes
-		if matches.len() == 1 && matches[0].len() == chunk.len() {
-			file = match await!(file::write(file.unwrap(), chunk)) {
-				Ok(f) => Some(f),
-				Err(err) => {
-					error!("[MultiPart Parser] Error writing to file: {}", err);
-					return Err(ParseError::Io(err));
-				}
-			};
-			continue;
+impl fmt::Display for MultiPartError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			MultiPartError::Io(ref err) => write!(f, "{}", err),
+			MultiPartError::Hyper(ref err) => write!(f, "{}", err),
+			MultiPartError::Malformed(reason) => write!(f, "Malformed multipart body: {}", reason),
+			MultiPartError::UnexpectedEof => write!(f, "Multipart body ended unexpectedly"),
+			MultiPartError::LimitExceeded(ref limit) => write!(f, "{}", limit),
+		}
+	}
+}
+
+impl error::Error for MultiPartError {
+	fn description(&self) -> &str {
+		match *self {
+			MultiPartError::Io(ref err) => err.description(),
+			MultiPartError::Hyper(ref err) => err.description(),
+			MultiPartError::Malformed(reason) => reason,
+			MultiPartError::UnexpectedEof => "Multipart body ended unexpectedly",
+			MultiPartError::LimitExceeded(_) => "Multipart limit exceeded",
 		}
+	}
+}
+
+impl From<io::Error> for MultiPartError {
+	fn from(err: io::Error) -> MultiPartError {
+		MultiPartError::Io(err)
+	}
+}
+
+impl From<hyper::Error> for MultiPartError {
+	fn from(err: hyper::Error) -> MultiPartError {
+		MultiPartError::Hyper(err)
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+	/// Skipping everything before the first boundary.
+	Preamble,
+	/// Just after a boundary, either a part or the end follows.
+	Boundary,
+	Headers,
+	Body,
+	Done,
+}
+
+struct Inner {
+	body: Body,
+	buf: BytesMut,
+	/// `\r\n--` followed by the boundary.
+	delimiter: Vec<u8>,
+	state: State,
+	/// incremented for every part, so a `Part` that's held on to can't read
+	/// the body of the parts after it.
+	part: usize,
+	/// bytes read from the body so far.
+	read: u64,
+	limit: Option<u64>,
+}
 
-		for matched in matches {
-			let split = crlf_regex
-				.split(&matched)
-				.map(Bytes::from)
-				.collect::<Vec<_>>();
+impl Inner {
+	/// Reads the next chunk of the request body into the buffer.
+	fn fill(&mut self) -> Poll<(), MultiPartError> {
+		match try_ready!(self.body.poll()) {
+			Some(chunk) => {
+				self.read += chunk.len() as u64;
+				if let Some(limit) = self.limit {
+					if self.read > limit {
+						return Err(MultiPartError::LimitExceeded(Limit::Size(limit)));
+					}
+				}
+				self.buf.extend_from_slice(&chunk);
+				Ok(Async::Ready(()))
+			}
+			None => Err(MultiPartError::UnexpectedEof),
+		}
+	}
 
-			for parts in split {
-				if parts.len() == 0 {
-					match state {
-						DecodeState::Header => {
-							state = DecodeState::Read(CharSet::None);
-							continue;
+	/// Parses the headers of the next part, skipping whatever is left of the
+	/// current one.
+	fn poll_head(&mut self) -> Poll<Option<PartHead>, MultiPartError> {
+		loop {
+			match self.state {
+				State::Preamble => {
+					match find(&self.buf, &self.delimiter) {
+						Some(index) => {
+							self.buf.split_to(index + self.delimiter.len());
+							self.state = State::Boundary;
 						}
-						DecodeState::Read(_) => {
-							state = DecodeState::Header;
-							continue;
+						None => {
+							// keep what could be the start of the delimiter.
+							let keep = self.delimiter.len() - 1;
+							if self.buf.len() > keep {
+								let len = self.buf.len() - keep;
+								self.buf.split_to(len);
+							}
+							try_ready!(self.fill());
 						}
-						DecodeState::Boundary => continue,
-					};
+					}
 				}
+				State::Boundary => {
+					// the boundary can be followed by whitespace, i.e transport padding.
+					let padding = self
+						.buf
+						.iter()
+						.take_while(|&&b| b == b' ' || b == b'\t')
+						.count();
+					self.buf.split_to(padding);
 
-				match state {
-					DecodeState::Header | DecodeState::Boundary => {
-						if let Ok(disp) = ContentDisposition::parse_header(&parts.to_vec().into()) {
-							if let DispositionType::Ext(ref contentType) = disp.disposition {
-								if contentType.to_lowercase().contains("content-type") {
-									let c_t = contentType.get(14..);
-									let mime = c_t.unwrap().to_string();
-									// map.insert("content-type".into(), mime.clone());
-									if let Some(ref mimes) = mimes {
-										let mime = mime.parse::<Mime>();
-										if !mime.is_ok() || !mimes.contains(&mime.unwrap()) {
-											error!("[MultiPart Parser] Unsupported Mime!");
-											return Err(ParseError::InvalidMime);
-										}
-									}
-								}
-							}
+					if self.buf.len() < 2 {
+						try_ready!(self.fill());
+						continue;
+					}
 
-							for param in disp.parameters {
-								match param {
-									DispositionParam::Ext(_, name) => {
-										map.insert("--param".into(), name);
-									}
-									DispositionParam::Filename(_, _, filename) => {
-										let filename = String::from_utf8(filename).unwrap();
-										map.insert("--file".into(), filename);
-									}
-								}
-							}
+					match &self.buf[..2] {
+						b"--" => self.state = State::Done,
+						// the CRLF is left in the buffer, so a part without headers
+						// ends with the same `\r\n\r\n` as any other.
+						b"\r\n" => self.state = State::Headers,
+						_ => {
+							return Err(MultiPartError::Malformed(
+								"expected a CRLF after the boundary",
+							))
 						}
-						state = DecodeState::Header;
 					}
-
-					DecodeState::Read(_) => {
-						match (map.remove("--file"), map.remove("--param")) {
-							(Some(filename), Some(param)) => {
-								let mut path = dir.clone();
-								path.push(&filename);
-								file = match await!(File::create(path)) {
-									Ok(f) => Some(f),
-									Err(err) => {
-										error!("[MultiPart Parser] Error creating file: {}", err);
-										return Err(ParseError::Io(err));
-									}
-								};
-								state = DecodeState::Read(CharSet::Binary(filename, param));
-							}
-							(None, Some(param)) => {
-								state = DecodeState::Read(CharSet::Utf8(param));
-							}
-							_ => {}
-						};
-
-						let charset = match state {
-							DecodeState::Read(ref r) => r.clone(),
-							_ => unreachable!(),
-						};
-
-						if &parts[..] != b"--" {
-							match charset {
-								CharSet::Utf8(param) => {
-									let val = String::from_utf8_lossy(&parts);
-									map.entry(param.clone()).or_insert("".into()).push_str(&val);
-								}
-								CharSet::Binary(filename, param) => {
-									file = match await!(file::write(file.unwrap(), parts)) {
-										Ok(f) => Some(f),
-										Err(err) => {
-											error!(
-												"[MultiPart Parser] Error writing to file: {}",
-												err
-											);
-											return Err(ParseError::Io(err));
-										}
-									};
-
-									if let None = map.get(&param) {
-										map.insert(param, filename);
-									}
-								}
-								_ => {}
-							}
+				}
+				State::Headers => {
+					match find(&self.buf, b"\r\n\r\n") {
+						Some(index) => {
+							let raw = self.buf.split_to(index + 4);
+							let raw = if index > 2 { &raw[2..index] } else { &[][..] };
+							let head = PartHead::parse(raw)?;
+							self.state = State::Body;
+							self.part += 1;
+							return Ok(Async::Ready(Some(head)));
+						}
+						None if self.buf.len() > MAX_HEADERS_SIZE => {
+							return Err(MultiPartError::Malformed("part headers are too large"));
 						}
+						None => try_ready!(self.fill()),
+					}
+				}
+				State::Body => {
+					// the current part wasn't read to the end.
+					while try_ready!(self.poll_chunk()).is_some() {}
+				}
+				State::Done => return Ok(Async::Ready(None)),
+			}
+		}
+	}
+
+	/// Returns the next chunk of the current part's body.
+	fn poll_chunk(&mut self) -> Poll<Option<Bytes>, MultiPartError> {
+		loop {
+			if self.state != State::Body {
+				return Ok(Async::Ready(None));
+			}
+
+			match find(&self.buf, &self.delimiter) {
+				Some(0) => {
+					self.buf.split_to(self.delimiter.len());
+					self.state = State::Boundary;
+					return Ok(Async::Ready(None));
+				}
+				Some(index) => return Ok(Async::Ready(Some(self.buf.split_to(index).freeze()))),
+				None => {
+					// everything but what could be the start of the delimiter is body.
+					let keep = self.delimiter.len() - 1;
+					if self.buf.len() > keep {
+						let len = self.buf.len() - keep;
+						return Ok(Async::Ready(Some(self.buf.split_to(len).freeze())));
 					}
+					try_ready!(self.fill());
 				}
 			}
 		}
 	}
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+	haystack
+		.windows(needle.len())
+		.position(|window| window == needle)
+}
+
+fn trim(bytes: &[u8]) -> &[u8] {
+	let start = bytes
+		.iter()
+		.position(|b| !b.is_ascii_whitespace())
+		.unwrap_or_else(|| bytes.len());
+	let end = bytes
+		.iter()
+		.rposition(|b| !b.is_ascii_whitespace())
+		.map_or(start, |end| end + 1);
+	&bytes[start..end]
+}
+
+/// The parsed headers of a part.
+#[derive(Clone, Debug)]
+struct PartHead {
+	name: String,
+	filename: Option<String>,
+	content_type: Option<Mime>,
+	headers: HeaderMap<HeaderValue>,
+}
+
+impl PartHead {
+	fn parse(raw: &[u8]) -> Result<PartHead, MultiPartError> {
+		let mut headers = HeaderMap::new();
+		for line in raw.split(|&b| b == b'\n') {
+			let line = trim(line);
+			if line.is_empty() {
+				continue;
+			}
+
+			let colon = match line.iter().position(|&b| b == b':') {
+				Some(colon) => colon,
+				None => return Err(MultiPartError::Malformed("invalid part header")),
+			};
+			let name = HeaderName::from_bytes(trim(&line[..colon]))
+				.map_err(|_| MultiPartError::Malformed("invalid part header name"))?;
+			let value = HeaderValue::from_bytes(trim(&line[colon + 1..]))
+				.map_err(|_| MultiPartError::Malformed("invalid part header value"))?;
+			headers.append(name, value);
+		}
+
+		let disposition = match headers.get(CONTENT_DISPOSITION) {
+			Some(disposition) => {
+				ContentDisposition::parse_header(&disposition.as_bytes().to_vec().into())
+					.map_err(|_| MultiPartError::Malformed("invalid Content-Disposition"))?
+			}
+			None => {
+				return Err(MultiPartError::Malformed(
+					"part is missing a Content-Disposition",
+				))
+			}
+		};
+
+		let (mut name, mut filename) = (None, None);
+		for param in disposition.parameters {
+			match param {
+				DispositionParam::Ext(ref key, ref value) if key.eq_ignore_ascii_case("name") => {
+					name = Some(value.clone());
+				}
+				DispositionParam::Filename(_, _, ref bytes) => {
+					filename = Some(String::from_utf8_lossy(bytes).into_owned());
+				}
+				_ => {}
+			}
+		}
+
+		let name = match name {
+			Some(name) => name,
+			None => return Err(MultiPartError::Malformed("part is missing a name")),
+		};
+		let content_type = headers
+			.get(CONTENT_TYPE)
+			.and_then(|value| value.to_str().ok())
+			.and_then(|value| value.parse::<Mime>().ok());
+
+		Ok(PartHead {
+			name,
+			filename,
+			content_type,
+			headers,
+		})
+	}
+}
+
+/// A `Stream` of the parts of a multipart/form-data body.
+///
+/// Every `Part` is itself a `Stream` of its body, the parts have to be read in
+/// order, polling for the next part skips whatever is left of the current one.
+///
+/// ```rust, ignore
+/// let boundary = ...; // the boundary param of the Content-Type header.
+/// let future = MultiPartStream::new(req.body(), boundary).for_each(|part| {
+/// 	match part.filename() {
+/// 		// saved under a random name, never the client's filename.
+/// 		Some(_) => Box::new(part.save("uploads").map(|_| ())),
+/// 		None => Box::new(part.buffer().map(|_| ())),
+/// 	}
+/// });
+/// ```
+pub struct MultiPartStream {
+	inner: Arc<Mutex<Inner>>,
+}
+
+impl MultiPartStream {
+	/// Creates a parser for the body, the boundary is the `boundary` param of
+	/// the request's Content-Type header.
+	pub fn new<B: AsRef<str>>(body: Body, boundary: B) -> Self {
+		let delimiter = format!("\r\n--{}", boundary.as_ref()).into_bytes();
+		// the first boundary isn't preceded by a CRLF, this way it's found
+		// like any other.
+		let mut buf = BytesMut::with_capacity(4096);
+		buf.extend_from_slice(b"\r\n");
+
+		let inner = Inner {
+			body,
+			buf,
+			delimiter,
+			state: State::Preamble,
+			part: 0,
+			read: 0,
+			limit: None,
+		};
+
+		Self {
+			inner: Arc::new(Mutex::new(inner)),
+		}
+	}
+
+	/// Fail with `Limit::Size` once more than `bytes` were read from the body,
+	/// whether or not the request had a Content-Length.
+	pub fn limit(self, bytes: u64) -> Self {
+		self.inner.lock().unwrap().limit = Some(bytes);
+		self
+	}
+}
+
+impl Stream for MultiPartStream {
+	type Item = Part;
+	type Error = MultiPartError;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		let mut inner = self.inner.lock().unwrap();
+		let head = try_ready!(inner.poll_head());
+
+		Ok(Async::Ready(head.map(|head| {
+			Part {
+				head,
+				id: inner.part,
+				inner: self.inner.clone(),
+				read: 0,
+				limit: None,
+			}
+		})))
+	}
+}
+
+/// A single part of a multipart body, and a `Stream` of its body.
+///
+/// Each part can be buffered in memory with `buffer`, written to a file with
+/// `save`, or forwarded to any `Sink`, since it's a `Stream`.
+pub struct Part {
+	head: PartHead,
+	id: usize,
+	inner: Arc<Mutex<Inner>>,
+	read: u64,
+	limit: Option<u64>,
+}
+
+/// A part that was written to disk by `Part::save`.
+#[derive(Clone, Debug)]
+pub struct SavedFile {
+	/// Where the file was saved.
+	pub path: PathBuf,
+	/// The size of the file in bytes.
+	pub size: u64,
+	/// The hex encoded SHA-256 of the file, if it was saved with `save_hashed`.
+	pub hash: Option<String>,
+}
+
+impl Part {
+	/// The name of the form field.
+	pub fn name(&self) -> &str {
+		&self.head.name
+	}
+
+	/// The filename supplied by the client, if this part is a file.
+	/// Don't use it as a path.
+	pub fn filename(&self) -> Option<&str> {
+		self.head.filename.as_ref().map(String::as_str)
+	}
+
+	/// The Content-Type of the part, if it was supplied.
+	pub fn content_type(&self) -> Option<&Mime> {
+		self.head.content_type.as_ref()
+	}
+
+	/// All the headers of the part.
+	pub fn headers(&self) -> &HeaderMap<HeaderValue> {
+		&self.head.headers
+	}
+
+	/// Fail once more than `bytes` were read from the part, with
+	/// `Limit::FileSize` for a file and `Limit::FieldSize` for a text field.
+	pub fn limit(mut self, bytes: u64) -> Self {
+		self.limit = Some(bytes);
+		self
+	}
+
+	/// Reads the whole body of the part into memory.
+	pub fn buffer(self) -> impl Future<Item = Bytes, Error = MultiPartError> {
+		self.fold(BytesMut::new(), |mut buf, chunk| {
+			buf.extend_from_slice(&chunk);
+			Ok::<_, MultiPartError>(buf)
+		})
+		.map(BytesMut::freeze)
+	}
+
+	/// Writes the body of the part to a new file in `dir`.
+	///
+	/// The file gets a unique, random name, keeping only the extension of the
+	/// client's filename, stripped of anything but ascii letters and digits.
+	/// It's always a new file, so an existing file or symlink with that name
+	/// is never written through.
+	/// The file is removed if the part can't be read to the end, or if the
+	/// future is dropped before it completes.
+	pub fn save<P: AsRef<Path>>(
+		self,
+		dir: P,
+	) -> impl Future<Item = SavedFile, Error = MultiPartError> {
+		self.write(dir.as_ref(), None)
+	}
+
+	/// Like `save`, but also computes the SHA-256 of the file as it's written.
+	pub fn save_hashed<P: AsRef<Path>>(
+		self,
+		dir: P,
+	) -> impl Future<Item = SavedFile, Error = MultiPartError> {
+		self.write(dir.as_ref(), Some(Sha256::default()))
+	}
+
+	fn write(
+		self,
+		dir: &Path,
+		hasher: Option<Sha256>,
+	) -> impl Future<Item = SavedFile, Error = MultiPartError> {
+		let path = dir.join(temp_name(self.filename()));
+
+		OpenOptions::new()
+			.write(true)
+			.create_new(true)
+			.open(path.clone())
+			.from_err()
+			.and_then(move |file| {
+				// from here on the file is ours, and removed unless it's complete.
+				let partial = PartialFile(Some(path));
+
+				self.fold((file, 0u64, hasher), |(file, size, mut hasher), chunk| {
+					if let Some(ref mut hasher) = hasher {
+						hasher.input(&chunk);
+					}
+					write_all(file, chunk)
+						.map(move |(file, chunk)| (file, size + chunk.len() as u64, hasher))
+						.from_err::<MultiPartError>()
+				})
+				.then(move |result| -> Result<SavedFile, MultiPartError> {
+					let (_, size, hasher) = result?;
+					let hash = hasher.map(|hasher| {
+						hasher
+							.result()
+							.iter()
+							.map(|byte| format!("{:02x}", byte))
+							.collect()
+					});
+
+					Ok(SavedFile {
+						path: partial.keep(),
+						size,
+						hash,
+					})
+				})
+			})
+	}
+}
+
+impl Stream for Part {
+	type Item = Bytes;
+	type Error = MultiPartError;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		let mut inner = self.inner.lock().unwrap();
+		if inner.part != self.id {
+			return Ok(Async::Ready(None));
+		}
+
+		let chunk = try_ready!(inner.poll_chunk());
+		if let (Some(chunk), Some(limit)) = (chunk.as_ref(), self.limit) {
+			self.read += chunk.len() as u64;
+			if self.read > limit {
+				let limit = match self.head.filename {
+					Some(_) => Limit::FileSize(limit),
+					None => Limit::FieldSize(limit),
+				};
+				return Err(MultiPartError::LimitExceeded(limit));
+			}
+		}
+
+		Ok(Async::Ready(chunk))
+	}
+}
+
+/// A file that's being written, it's removed when dropped unless it's kept.
+struct PartialFile(Option<PathBuf>);
+
+impl PartialFile {
+	fn keep(mut self) -> PathBuf {
+		self.0.take().unwrap()
+	}
+}
+
+impl Drop for PartialFile {
+	fn drop(&mut self) {
+		if let Some(ref path) = self.0 {
+			let _ = fs::remove_file(path);
+		}
+	}
+}
+
+/// A unique file name that can't be guessed, with the sanitized extension of
+/// the client's filename.
+fn temp_name(filename: Option<&str>) -> String {
+	let now = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap_or_default();
+	// std seeds `RandomState` from the OS, so the hash can't be predicted.
+	let mut hasher = RandomState::new().build_hasher();
+	hasher.write_u64(now.as_secs());
+	hasher.write_u32(now.subsec_nanos());
+	hasher.write_usize(UPLOADS.fetch_add(1, Ordering::Relaxed));
+	let name = format!("upload-{:x}-{:016x}", now.as_secs(), hasher.finish());
+
+	let extension = filename
+		.and_then(|filename| Path::new(filename).extension())
+		.and_then(|extension| extension.to_str())
+		.map(|extension| {
+			extension
+				.chars()
+				.filter(|c| c.is_ascii_alphanumeric())
+				.take(16)
+				.collect::<String>()
+		});
+
+	match extension {
+		Some(ref extension) if !extension.is_empty() => format!("{}.{}", name, extension),
+		_ => name,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::{stream, sync::mpsc};
+	use hyper::Chunk;
+	use mime;
+	use std::{
+		env,
+		time::{Duration, Instant},
+	};
+	use tokio::{runtime::Runtime, timer::Delay};
+
+	const BODY: &str = "preamble\r\n\
+	                    --xyz\r\n\
+	                    Content-Disposition: form-data; name=\"title\"\r\n\
+	                    \r\n\
+	                    hello --xy world\r\n\
+	                    --xyz\r\n\
+	                    Content-Disposition: form-data; name=\"file\"; filename=\"../../etc/a.t x?t\"\r\n\
+	                    Content-Type: text/plain\r\n\
+	                    \r\n\
+	                    contents\r\n\
+	                    --xyz--\r\n";
+
+	/// Sends the body in chunks of `size` bytes, so boundaries straddle chunks.
+	fn body(size: usize) -> Body {
+		let chunks = BODY
+			.as_bytes()
+			.chunks(size)
+			.map(|chunk| chunk.to_vec())
+			.collect::<Vec<_>>();
+		Body::wrap_stream(stream::iter_ok::<_, hyper::Error>(chunks))
+	}
 
-	Ok(map)
+	#[test]
+	fn it_parses_parts_across_chunks() {
+		for size in 1..BODY.len() {
+			let parts = MultiPartStream::new(body(size), "xyz")
+				.and_then(|part| {
+					let name = part.name().to_owned();
+					let filename = part.filename().map(String::from);
+					let content_type = part.content_type().cloned();
+					part.buffer()
+						.map(move |value| (name, filename, content_type, value))
+				})
+				.collect()
+				.wait()
+				.unwrap();
+
+			assert_eq!(parts.len(), 2);
+			assert_eq!(parts[0].0, "title");
+			assert_eq!(&parts[0].3[..], b"hello --xy world");
+			assert_eq!(parts[1].0, "file");
+			assert_eq!(parts[1].1, Some("../../etc/a.t x?t".to_owned()));
+			assert_eq!(parts[1].2, Some(mime::TEXT_PLAIN));
+			assert_eq!(&parts[1].3[..], b"contents");
+		}
+	}
+
+	#[test]
+	fn it_skips_unread_parts() {
+		let names = MultiPartStream::new(body(7), "xyz")
+			.map(|part| part.name().to_owned())
+			.collect()
+			.wait()
+			.unwrap();
+
+		assert_eq!(names, vec!["title", "file"]);
+	}
+
+	#[test]
+	fn it_fails_on_truncated_bodies() {
+		let body = Body::from(&BODY[..BODY.len() - 10]);
+		let result = MultiPartStream::new(body, "xyz")
+			.and_then(|part| part.buffer())
+			.collect()
+			.wait();
+
+		assert!(result.is_err());
+	}
+
+	#[test]
+	fn it_enforces_limits_while_streaming() {
+		let result = MultiPartStream::new(body(16), "xyz")
+			.limit(64)
+			.and_then(|part| part.buffer())
+			.collect()
+			.wait();
+		match result {
+			Err(MultiPartError::LimitExceeded(Limit::Size(64))) => {}
+			result => panic!("unexpected result {:?}", result),
+		}
+
+		let result = MultiPartStream::new(body(16), "xyz")
+			.and_then(|part| part.limit(4).buffer())
+			.collect()
+			.wait();
+		match result {
+			Err(MultiPartError::LimitExceeded(Limit::FieldSize(4))) => {}
+			result => panic!("unexpected result {:?}", result),
+		}
+	}
+
+	#[test]
+	fn it_skips_transport_padding_after_boundaries() {
+		let body = "--xyz \t\r\n\
+		            Content-Disposition: form-data; name=\"title\"\r\n\
+		            \r\n\
+		            hello\r\n\
+		            --xyz--  \r\n";
+		let values = MultiPartStream::new(Body::from(body), "xyz")
+			.and_then(|part| part.buffer())
+			.collect()
+			.wait()
+			.unwrap();
+
+		assert_eq!(values.len(), 1);
+		assert_eq!(&values[0][..], b"hello");
+	}
+
+	#[test]
+	fn it_sanitizes_saved_file_names() {
+		let name = temp_name(Some("../../etc/a.t x?t"));
+		assert!(name.starts_with("upload-"));
+		assert!(name.ends_with(".txt"));
+		assert!(!name.contains('/'));
+
+		assert!(!temp_name(Some("passwd")).contains('.'));
+		assert!(!temp_name(None).contains('.'));
+		assert_ne!(temp_name(None), temp_name(None));
+	}
+
+	#[test]
+	fn it_removes_partial_files_when_the_upload_is_dropped() {
+		let dir = env::temp_dir().join(format!("arc-reactor-partial-{}", ::std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+
+		// the body never ends, the client is still sending.
+		let (sender, receiver) = mpsc::unbounded::<Chunk>();
+		let head = "--xyz\r\n\
+		            Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+		            \r\n";
+		sender.unbounded_send(head.into()).unwrap();
+		sender.unbounded_send("x".repeat(64).into()).unwrap();
+		let body = Body::wrap_stream(receiver.map_err(|_| io::Error::from(io::ErrorKind::Other)));
+
+		let mut runtime = Runtime::new().unwrap();
+		let part = runtime
+			.block_on(MultiPartStream::new(body, "xyz").into_future())
+			.ok()
+			.unwrap()
+			.0
+			.unwrap();
+		let timeout = Delay::new(Instant::now() + Duration::from_millis(200));
+		let upload = runtime
+			.block_on(part.save(dir.clone()).select2(timeout))
+			.ok()
+			.unwrap();
+
+		assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+		drop(upload);
+		assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+		fs::remove_dir_all(dir).unwrap();
+	}
 }
@@ -1,14 +1,54 @@
-use core::{file, Request, Response};
+use super::compression::{preferred_encoding, Encoding};
+use core::{Request, Response};
 use futures::{future, prelude::*};
 use hyper::{
-	header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE},
+	header::{
+		HeaderValue,
+		ACCEPT,
+		ACCEPT_ENCODING,
+		CACHE_CONTROL,
+		CONTENT_ENCODING,
+		CONTENT_TYPE,
+		VARY,
+	},
+	Body,
 	Method,
+	StatusCode,
 };
+use mime::Mime;
 use mime_guess::guess_mime_type;
-use percent_encoding::percent_decode;
+use percent_encoding::{percent_decode, utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
 use proto::{MiddleWare, MiddleWareFuture};
-use std::path::PathBuf;
-use tokio::{fs::File, io::ErrorKind};
+use serde_json::to_string;
+use std::{
+	fs,
+	path::{Component, Path, PathBuf},
+};
+use tokio_threadpool::blocking;
+
+/// The precompressed siblings that are looked for, in order of preference.
+const SIBLINGS: [(Encoding, &str); 2] = [(Encoding::Brotli, "br"), (Encoding::Gzip, "gz")];
+
+/// Selects the files a `Cache-Control` policy applies to.
+#[derive(Clone, Debug)]
+enum CacheRule {
+	Prefix(String),
+	Mime(String),
+}
+
+impl CacheRule {
+	fn matches(&self, path: &str, mime_type: &Mime) -> bool {
+		match *self {
+			CacheRule::Prefix(ref prefix) => path.starts_with(prefix.as_str()),
+			CacheRule::Mime(ref pattern) => {
+				let mut parts = pattern.splitn(2, '/');
+				let (type_, subtype) = (parts.next().unwrap_or(""), parts.next().unwrap_or("*"));
+				type_ == mime_type.type_().as_str()
+					&& (subtype == "*" || subtype == mime_type.subtype().as_str())
+			}
+		}
+	}
+}
 
 /// A static File Server implemented as a Middleware<Request>
 #[derive(Clone, Debug)]
@@ -19,111 +59,725 @@ pub struct StaticFileServer {
 	pub root: &'static str,
 	/// Path to folder to serve your static files from.
 	pub public: PathBuf,
+	/// `public` with its symlinks resolved, `None` if it doesn't exist.
+	canonical_public: Option<PathBuf>,
+	cache_control: Vec<(CacheRule, HeaderValue)>,
+	precompressed: bool,
+	directory_listing: bool,
+	spa_fallback: Option<PathBuf>,
+	allow_hidden: bool,
+	follow_symlinks: bool,
 }
 
 impl StaticFileServer {
 	/// Creates a StaticFileServer with the given
 	/// root and pathbuf.
+	///
+	/// `public` is resolved once, here, so it must already exist.
 	pub fn new(root: &'static str, public: PathBuf) -> Self {
-		Self { root, public }
+		let canonical_public = fs::canonicalize(&public).ok();
+		Self {
+			root,
+			public,
+			canonical_public,
+			cache_control: Vec::new(),
+			precompressed: false,
+			directory_listing: false,
+			spa_fallback: None,
+			allow_hidden: false,
+			follow_symlinks: true,
+		}
+	}
+
+	/// Serve hidden files and directories, i.e those whose name begins with a
+	/// `.`, they are refused by default.
+	pub fn allow_hidden(mut self, allow: bool) -> Self {
+		self.allow_hidden = allow;
+		self
+	}
+
+	/// Follow symlinks inside `public`, this is the default.
+	/// Even when followed, a symlink can't lead to a file outside `public`.
+	pub fn follow_symlinks(mut self, follow: bool) -> Self {
+		self.follow_symlinks = follow;
+		self
+	}
+
+	/// Joins the decoded request path onto `public` one component at a time,
+	/// returns `None` if any component is rejected.
+	fn join(&self, relative: &str) -> Option<PathBuf> {
+		if relative.contains('\0') {
+			return None;
+		}
+
+		let mut pathbuf = self.public.clone();
+		for component in relative.split(|c| c == '/' || c == '\\') {
+			match component {
+				"" | "." => continue,
+				".." => return None,
+				_ => {}
+			}
+
+			if component.starts_with('.') && !self.allow_hidden {
+				return None;
+			}
+
+			// anything that isn't a plain file name, e.g `C:` on windows, would
+			// replace the path when pushed.
+			let mut components = Path::new(component).components();
+			match (components.next(), components.next()) {
+				(Some(Component::Normal(_)), None) => pathbuf.push(component),
+				_ => return None,
+			}
+		}
+
+		Some(pathbuf)
+	}
+
+	/// Returns true if the path, with symlinks resolved, is still inside
+	/// `public`. When symlinks aren't followed, it also checks that none of
+	/// the components are symlinks.
+	fn is_inside(&self, path: &Path) -> bool {
+		let public = match self.canonical_public {
+			Some(ref public) => public,
+			None => return false,
+		};
+
+		if !self.follow_symlinks {
+			let relative = match path.strip_prefix(&self.public) {
+				Ok(relative) => relative,
+				Err(_) => return false,
+			};
+
+			let mut current = self.public.clone();
+			for component in relative.components() {
+				current.push(component);
+				match fs::symlink_metadata(&current) {
+					Ok(ref meta) if meta.file_type().is_symlink() => return false,
+					Ok(_) => {}
+					// the rest of the path doesn't exist.
+					Err(_) => break,
+				}
+			}
+		}
+
+		match fs::canonicalize(path) {
+			Ok(path) => path.starts_with(public),
+			// it doesn't exist, so it would be a 404 anyway.
+			Err(_) => true,
+		}
+	}
+
+	/// List the contents of directories that don't have an `index.html`.
+	/// The listing is JSON if the client accepts `application/json` but not
+	/// `text/html`, HTML otherwise.
+	pub fn directory_listing(mut self, enabled: bool) -> Self {
+		self.directory_listing = enabled;
+		self
+	}
+
+	/// Serve this file, relative to `public`, for paths under the root that
+	/// don't exist and don't have an extension, so client-side routing works
+	/// in single page apps. e.g `.spa_fallback("index.html")`
+	pub fn spa_fallback<P: Into<PathBuf>>(mut self, fallback: P) -> Self {
+		self.spa_fallback = Some(fallback.into());
+		self
+	}
+
+	/// Serve precompressed siblings, e.g `app.js.br` or `app.js.gz` for
+	/// `app.js`, to clients that accept the encoding.
+	/// The plain file is served if there's no acceptable sibling.
+	pub fn precompressed(mut self, enabled: bool) -> Self {
+		self.precompressed = enabled;
+		self
+	}
+
+	/// Sets the `Cache-Control` header for files whose path, relative to
+	/// `public`, begins with `prefix`. e.g
+	/// `.cache_control_prefix("fonts/", "public, max-age=31536000, immutable")`
+	///
+	/// Policies are checked in the order they were added, the first match wins.
+	pub fn cache_control_prefix(mut self, prefix: &str, value: &'static str) -> Self {
+		self.cache_control.push((
+			CacheRule::Prefix(prefix.to_owned()),
+			HeaderValue::from_static(value),
+		));
+		self
+	}
+
+	/// Sets the `Cache-Control` header for files of the given mime type,
+	/// `type/*` matches every subtype. e.g `.cache_control_mime("image/*", "max-age=86400")`
+	///
+	/// Policies are checked in the order they were added, the first match wins.
+	pub fn cache_control_mime(mut self, mime_type: &str, value: &'static str) -> Self {
+		self.cache_control.push((
+			CacheRule::Mime(mime_type.to_lowercase()),
+			HeaderValue::from_static(value),
+		));
+		self
+	}
+
+	/// Picks the precompressed sibling of the file the client prefers.
+	fn precompressed_sibling(&self, req: &Request, path: &Path) -> Option<(Encoding, PathBuf)> {
+		if !self.precompressed {
+			return None;
+		}
+
+		let accept = req
+			.headers()
+			.get_all(ACCEPT_ENCODING)
+			.iter()
+			.filter_map(|value| value.to_str().ok())
+			.collect::<Vec<_>>()
+			.join(",");
+		let available = SIBLINGS
+			.iter()
+			.filter(|&&(_, extension)| {
+				let sibling = sibling(path, extension);
+				sibling.is_file() && self.is_inside(&sibling)
+			})
+			.map(|&(encoding, _)| encoding)
+			.collect::<Vec<_>>();
+
+		let encoding = preferred_encoding(&accept, &available)?;
+		let extension = SIBLINGS
+			.iter()
+			.find(|&&(e, _)| e == encoding)
+			.map(|&(_, extension)| extension)?;
+
+		Some((encoding, sibling(path, extension)))
+	}
+
+	/// Resolves the path, relative to the root, to the file to serve or to a
+	/// directory listing. It hits the filesystem, so it's run in `blocking`.
+	fn resolve(&self, req: &Request, relative: &str, is_head: bool) -> Resolved {
+		let mut pathbuf = match self.join(relative) {
+			Some(pathbuf) => pathbuf,
+			None => return Resolved::NotFound,
+		};
+
+		if pathbuf.is_dir() {
+			let index = pathbuf.join("index.html");
+			if self.directory_listing && !index.is_file() {
+				if !self.is_inside(&pathbuf) {
+					return Resolved::NotFound;
+				}
+				return Resolved::Listing(listing(req, &pathbuf, is_head, self.allow_hidden));
+			}
+			pathbuf = index;
+		} else if !pathbuf.exists() {
+			// paths with an extension are assets, those should still 404.
+			let is_route = relative
+				.rsplit('/')
+				.next()
+				.map_or(true, |name| !name.contains('.'));
+			if let Some(ref fallback) = self.spa_fallback {
+				if is_route {
+					pathbuf = self.public.join(fallback);
+				}
+			}
+		}
+
+		if !self.is_inside(&pathbuf) {
+			return Resolved::NotFound;
+		}
+
+		let mime_type = guess_mime_type(&pathbuf);
+		let cache_control = self.cache_control(relative, &mime_type);
+		let (encoding, pathbuf) = match self.precompressed_sibling(req, &pathbuf) {
+			Some((encoding, sibling)) => (Some(encoding), sibling),
+			None => (None, pathbuf),
+		};
+
+		Resolved::File {
+			pathbuf,
+			mime_type,
+			cache_control,
+			encoding,
+		}
+	}
+
+	fn cache_control(&self, path: &str, mime_type: &Mime) -> Option<HeaderValue> {
+		self.cache_control
+			.iter()
+			.find(|&&(ref rule, _)| rule.matches(path, mime_type))
+			.map(|&(_, ref value)| value.clone())
 	}
 }
 
-impl MiddleWare<Request> for StaticFileServer {
-	fn call(&self, req: Request) -> MiddleWareFuture<Request> {
-		let path = {
-			req.path()
-				.get(1..)
-				.and_then(|path| {
-					Some(
-						percent_decode(path.as_ref())
-							.decode_utf8_lossy()
-							.into_owned(),
-					)
-				})
-				.and_then(|path| {
-					if path.contains("../") {
-						None
-					} else {
-						Some(path)
+/// What a request for a path under the root resolves to.
+enum Resolved {
+	NotFound,
+	Listing(Response),
+	File {
+		pathbuf: PathBuf,
+		mime_type: Mime,
+		cache_control: Option<HeaderValue>,
+		encoding: Option<Encoding>,
+	},
+}
+
+/// Appends the extension to the file name, e.g `app.js` => `app.js.br`.
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+	let mut path = path.as_os_str().to_owned();
+	path.push(".");
+	path.push(extension);
+	PathBuf::from(path)
+}
+
+/// Escapes text for use in html.
+fn escape_html(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+	for c in text.chars() {
+		match c {
+			'&' => escaped.push_str("&amp;"),
+			'<' => escaped.push_str("&lt;"),
+			'>' => escaped.push_str("&gt;"),
+			'"' => escaped.push_str("&quot;"),
+			'\'' => escaped.push_str("&#39;"),
+			c => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+/// Lists the directory as HTML or JSON depending on the `Accept` header,
+/// hidden files are left out unless they're allowed.
+fn listing(req: &Request, dir: &Path, is_head: bool, allow_hidden: bool) -> Response {
+	let mut entries = match fs::read_dir(dir) {
+		Ok(entries) => {
+			entries
+				.filter_map(|entry| entry.ok())
+				.filter_map(|entry| {
+					let name = entry.file_name().into_string().ok()?;
+					if name.starts_with('.') && !allow_hidden {
+						return None;
 					}
+					let meta = entry.metadata().ok()?;
+					Some((name, meta.is_dir(), meta.len()))
 				})
-		};
+				.collect::<Vec<_>>()
+		}
+		Err(err) => {
+			error!("Error reading directory: {}", err);
+			return Response::new().with_status(500);
+		}
+	};
+	// directories first, then by name.
+	entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+	let accept = req
+		.headers()
+		.get(ACCEPT)
+		.and_then(|value| value.to_str().ok())
+		.unwrap_or("");
+	let wants_json = accept.contains("application/json") && !accept.contains("text/html");
 
-		let prefix = match path {
-			Some(ref r) => r.get(..self.root.len()),
-			None => None,
+	let mut base = req.path().to_owned();
+	if !base.ends_with('/') {
+		base.push('/');
+	}
+
+	let (content_type, body) = if wants_json {
+		let entries = entries
+			.iter()
+			.map(|&(ref name, is_dir, size)| {
+				json!({
+					"name": name,
+					"dir": is_dir,
+					"size": size,
+				})
+			})
+			.collect::<Vec<_>>();
+		("application/json", to_string(&entries).unwrap())
+	} else {
+		let title = escape_html(&percent_decode(base.as_bytes()).decode_utf8_lossy());
+		let mut html = format!(
+			"<!DOCTYPE html>\n<html>\n\
+			 <head><meta charset=\"utf-8\"><title>Index of {0}</title></head>\n\
+			 <body>\n<h1>Index of {0}</h1>\n<ul>\n\
+			 <li><a href=\"../\">../</a></li>\n",
+			title
+		);
+		for &(ref name, is_dir, _) in &entries {
+			let slash = if is_dir { "/" } else { "" };
+			html.push_str(&format!(
+				"<li><a href=\"{}{}{}\">{}{}</a></li>\n",
+				base,
+				utf8_percent_encode(name, PATH_SEGMENT_ENCODE_SET),
+				slash,
+				escape_html(name),
+				slash
+			));
+		}
+		html.push_str("</ul>\n</body>\n</html>\n");
+		("text/html; charset=utf-8", html)
+	};
+
+	let mut res = Response::for_request(req).with_text(body);
+	res.headers_mut()
+		.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+	if is_head {
+		res.set_body(Body::empty());
+	}
+	res
+}
+
+impl MiddleWare<Request> for StaticFileServer {
+	fn call(&self, req: Request) -> MiddleWareFuture<Request> {
+		let path = req
+			.path()
+			.get(1..)
+			.map(|path| {
+				percent_decode(path.as_ref())
+					.decode_utf8_lossy()
+					.into_owned()
+			})
+			.unwrap_or_default();
+
+		// the path relative to the root, if the request is for a file under it.
+		let relative = match path.get(..self.root.len()) {
+			Some(prefix) if prefix == self.root => {
+				match &path[self.root.len()..] {
+					"" => Some(""),
+					rest if rest.starts_with('/') => Some(&rest[1..]),
+					_ => None,
+				}
+			}
+			_ => None,
 		};
 
-		if prefix == Some(self.root) {
+		if let Some(relative) = relative {
 			// supported http-methods
 			let method = { req.method().clone() };
 			if method != Method::GET && method != Method::HEAD {
 				return Box::new(future::ok(req));
 			}
 
-			let mut pathbuf = self.public.clone();
-			if let Some(ref path) = path {
-				if let Some(ref path) = path.get(self.root.len() + 1..) {
-					pathbuf.push(path);
+			let is_head = method == Method::HEAD;
+			let precompressed = self.precompressed;
+			let res = Response::for_request(&req);
+			let server = self.clone();
+			let relative = relative.to_owned();
+			// the path checks make blocking calls, so they're moved off the
+			// reactor.
+			let resolved = future::poll_fn(move || {
+				match blocking(|| server.resolve(&req, &relative, is_head)) {
+					Ok(resolved) => Ok(resolved),
+					// not on a threadpool, e.g a current_thread runtime.
+					Err(_) => Ok(Async::Ready(server.resolve(&req, &relative, is_head))),
 				}
-			}
-			if pathbuf.is_dir() {
-				pathbuf.push("index.html");
-			}
+			});
 
-			let path_clone = pathbuf.clone();
+			let future = resolved.and_then(move |resolved| -> MiddleWareFuture<Request> {
+				let (pathbuf, mime_type, cache_control, encoding) = match resolved {
+					Resolved::NotFound => return Box::new(future::err(res.with_status(404))),
+					Resolved::Listing(res) => return Box::new(future::err(res)),
+					Resolved::File {
+						pathbuf,
+						mime_type,
+						cache_control,
+						encoding,
+					} => (pathbuf, mime_type, cache_control, encoding),
+				};
 
-			match method {
-				Method::GET => {
-					// if a MiddleWare<T> returns Err(Response)
-					// that reponse is forwarded directly to the client.
-					return Box::new(Response::new().with_file(pathbuf).then(|res| {
-						match res {
-							Ok(res) | Err(res) => Err(res),
+				let future = res.with_file(pathbuf).then(move |res| {
+					let mut res = match res {
+						Ok(res) | Err(res) => res,
+					};
+
+					if res.status().is_success() || res.status() == StatusCode::NOT_MODIFIED {
+						if let Some(value) = cache_control {
+							res.headers_mut().insert(CACHE_CONTROL, value);
 						}
-					}));
-				}
-				Method::HEAD => {
-					let future = File::open(path_clone)
-						.and_then(file::metadata)
-						.then(|result| {
-							match result {
-								Ok((_, meta)) => {
-									let mut res = Response::new();
-									let mime_type = guess_mime_type(pathbuf);
-									res.headers_mut().insert(
-										CONTENT_LENGTH,
-										HeaderValue::from_str(&meta.len().to_string()).unwrap(),
-									);
-									res.headers_mut().insert(
-										CONTENT_TYPE,
-										HeaderValue::from_str(mime_type.as_ref()).unwrap(),
-									);
-									return Err(res);
-								}
-								Err(err) => {
-									error!("Error opening file: {}", err);
-									match err.kind() {
-										ErrorKind::NotFound => {
-											let mut res = Response::new().with_status(404);
-											return Err(res);
-										}
-										_ => {
-											let mut res = Response::new().with_status(500);
-											return Err(res);
-										}
-									}
-								}
+						if precompressed {
+							res.headers_mut()
+								.append(VARY, HeaderValue::from_static("Accept-Encoding"));
+						}
+					}
+
+					if let Some(encoding) = encoding {
+						if res.status().is_success() {
+							res.headers_mut().insert(
+								CONTENT_ENCODING,
+								HeaderValue::from_static(encoding.as_str()),
+							);
+							// the sibling's content type would be application/gzip, so the
+							// original file's is used, unless it's a multipart response.
+							let is_multipart = res
+								.headers()
+								.get(CONTENT_TYPE)
+								.map_or(false, |value| value.as_bytes().starts_with(b"multipart/"));
+							if !is_multipart {
+								res.headers_mut().insert(
+									CONTENT_TYPE,
+									HeaderValue::from_str(mime_type.as_ref()).unwrap(),
+								);
 							}
-						});
+						}
+					}
 
-					return Box::new(future);
-				}
-				_ => {}
-			}
+					// the headers are kept, so HEAD gets the same Content-Length and
+					// validators as GET.
+					if is_head {
+						res.set_body(Body::empty());
+					}
+
+					// if a MiddleWare<T> returns Err(Response)
+					// that reponse is forwarded directly to the client.
+					Err(res)
+				});
+
+				Box::new(future)
+			});
+
+			return Box::new(future);
 		}
 
 		Box::new(future::ok(req))
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use http::request::Builder;
+	use hyper::header::{CONTENT_LENGTH, ETAG};
+	use serde_json::{from_slice, Value};
+	use std::env;
+	use tokio::runtime::Runtime;
+
+	/// Creates a fresh `public` directory with an `app.js`, a `.env` and a
+	/// `secret.txt` outside of it.
+	fn setup(name: &str) -> (PathBuf, PathBuf) {
+		let dir = env::temp_dir().join(format!("arc-reactor-{}-{}", name, ::std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		let public = dir.join("public");
+		fs::create_dir_all(public.join("js")).unwrap();
+		fs::write(public.join("js/app.js"), "console.log(1)").unwrap();
+		fs::write(public.join(".env"), "SECRET=1").unwrap();
+		fs::write(dir.join("secret.txt"), "secret").unwrap();
+		(dir, public)
+	}
+
+	fn get(server: &StaticFileServer, path: &str) -> Result<Request, Response> {
+		request(server, path, &[])
+	}
+
+	fn request(
+		server: &StaticFileServer,
+		path: &str,
+		headers: &[(&str, &str)],
+	) -> Result<Request, Response> {
+		let mut builder = Builder::new();
+		builder.uri(path);
+		for &(name, value) in headers {
+			builder.header(name, value);
+		}
+		let req: Request = builder.body(Default::default()).unwrap().into();
+		Runtime::new().unwrap().block_on(server.call(req))
+	}
+
+	#[test]
+	fn it_rejects_traversal() {
+		let (dir, public) = setup("traversal");
+		let server = StaticFileServer::new("static", public.clone());
+
+		assert!(server.join("js/app.js").is_some());
+		assert!(server.join("../secret.txt").is_none());
+		assert!(server.join("js/..").is_none());
+		assert!(server.join("..\\secret.txt").is_none());
+		assert!(server.join("js/app.js\0.png").is_none());
+		assert_eq!(
+			server.join("/etc/passwd"),
+			Some(public.join("etc").join("passwd"))
+		);
+
+		let res = get(&server, "/static/js/app.js").unwrap_err();
+		assert_eq!(res.status(), StatusCode::OK);
+
+		for path in &[
+			"/static/../secret.txt",
+			"/static/js/%2e%2e/%2e%2e/secret.txt",
+			"/static/%2e%2e%5csecret.txt",
+			"/static/js/app.js%00.png",
+		] {
+			let res = get(&server, path).unwrap_err();
+			assert_eq!(res.status(), StatusCode::NOT_FOUND, "{}", path);
+		}
+
+		// not under the root, so it's left to the router.
+		assert!(get(&server, "/staticfoo").is_ok());
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn it_rejects_hidden_files_unless_allowed() {
+		let (dir, public) = setup("hidden");
+		let server = StaticFileServer::new("static", public.clone());
+
+		let res = get(&server, "/static/.env").unwrap_err();
+		assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+		let server = server.allow_hidden(true);
+		let res = get(&server, "/static/.env").unwrap_err();
+		assert_eq!(res.status(), StatusCode::OK);
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn it_responds_with_304_for_fresh_copies() {
+		let (dir, public) = setup("not-modified");
+		let server = StaticFileServer::new("static", public.clone());
+
+		let res = get(&server, "/static/js/app.js").unwrap_err();
+		assert_eq!(res.status(), StatusCode::OK);
+		let etag = res.headers()[ETAG].to_str().unwrap().to_owned();
+
+		let res = request(&server, "/static/js/app.js", &[("if-none-match", &etag)]).unwrap_err();
+		assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn it_serves_precompressed_siblings() {
+		let (dir, public) = setup("precompressed");
+		fs::write(public.join("js/app.js.br"), "brotli").unwrap();
+		fs::write(public.join("js/app.js.gz"), "gzipped!").unwrap();
+		let server = StaticFileServer::new("static", public.clone()).precompressed(true);
+
+		for &(accept, encoding, length) in &[("gzip, br", "br", "6"), ("gzip", "gzip", "8")] {
+			let res =
+				request(&server, "/static/js/app.js", &[("accept-encoding", accept)]).unwrap_err();
+			assert_eq!(res.status(), StatusCode::OK);
+			assert_eq!(res.headers()[CONTENT_ENCODING], encoding);
+			assert_eq!(res.headers()[CONTENT_LENGTH], length);
+			// the type of the original file, whichever sibling is sent.
+			assert_eq!(res.headers()[CONTENT_TYPE], guess_mime_type("app.js").as_ref());
+			assert_eq!(res.headers()[VARY], "Accept-Encoding");
+		}
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn it_serves_the_plain_file_if_no_sibling_is_acceptable() {
+		let (dir, public) = setup("precompressed-fallback");
+		fs::write(public.join("js/app.js.gz"), "gzipped!").unwrap();
+		let server = StaticFileServer::new("static", public.clone()).precompressed(true);
+
+		for headers in &[&[][..], &[("accept-encoding", "br, gzip;q=0")][..]] {
+			let res = request(&server, "/static/js/app.js", headers).unwrap_err();
+			assert_eq!(res.status(), StatusCode::OK);
+			assert!(!res.headers().contains_key(CONTENT_ENCODING));
+			assert_eq!(res.headers()[CONTENT_LENGTH], "14");
+			assert_eq!(res.headers()[VARY], "Accept-Encoding");
+		}
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	fn body(res: Response) -> Vec<u8> {
+		res.body().concat2().wait().unwrap().to_vec()
+	}
+
+	#[test]
+	fn it_lists_directories_as_html() {
+		let (dir, public) = setup("listing-html");
+		fs::write(public.join("js/.secret.js"), "secret").unwrap();
+		fs::create_dir(public.join("js/vendor")).unwrap();
+		let server = StaticFileServer::new("static", public.clone()).directory_listing(true);
+
+		let res = request(&server, "/static/js", &[("accept", "text/html")]).unwrap_err();
+		assert_eq!(res.status(), StatusCode::OK);
+		assert_eq!(res.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+
+		let html = String::from_utf8(body(res)).unwrap();
+		assert!(html.contains("<title>Index of /static/js/</title>"));
+		let vendor = html
+			.find("<a href=\"/static/js/vendor/\">vendor/</a>")
+			.unwrap();
+		let app = html
+			.find("<a href=\"/static/js/app.js\">app.js</a>")
+			.unwrap();
+		// directories come first.
+		assert!(vendor < app);
+		assert!(!html.contains(".secret.js"));
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn it_lists_directories_as_json() {
+		let (dir, public) = setup("listing-json");
+		fs::write(public.join("js/.secret.js"), "secret").unwrap();
+		let server = StaticFileServer::new("static", public.clone()).directory_listing(true);
+
+		let res = request(&server, "/static/js/", &[("accept", "application/json")]).unwrap_err();
+		assert_eq!(res.status(), StatusCode::OK);
+		assert_eq!(res.headers()[CONTENT_TYPE], "application/json");
+		let entries: Value = from_slice(&body(res)).unwrap();
+		assert_eq!(
+			entries,
+			json!([{ "name": "app.js", "dir": false, "size": 14 }])
+		);
+
+		// hidden files are listed once they're allowed.
+		let server = server.allow_hidden(true);
+		let res = request(&server, "/static/js/", &[("accept", "application/json")]).unwrap_err();
+		let entries: Value = from_slice(&body(res)).unwrap();
+		assert_eq!(entries.as_array().unwrap().len(), 2);
+
+		// the listing is off by default.
+		let server = StaticFileServer::new("static", public.clone());
+		let res = get(&server, "/static/js/").unwrap_err();
+		assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn it_serves_the_spa_fallback_for_unknown_routes() {
+		let (dir, public) = setup("spa-fallback");
+		fs::write(public.join("index.html"), "<div id=\"app\"></div>").unwrap();
+		let server = StaticFileServer::new("static", public.clone()).spa_fallback("index.html");
+
+		for path in &["/static/dashboard", "/static/users/1/settings"] {
+			let res = get(&server, path).unwrap_err();
+			assert_eq!(res.status(), StatusCode::OK, "{}", path);
+			assert_eq!(res.headers()[CONTENT_TYPE], "text/html");
+		}
+
+		// a missing asset is still a 404.
+		let res = get(&server, "/static/js/missing.js").unwrap_err();
+		assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn it_does_not_follow_symlinks_out_of_public() {
+		use std::os::unix::fs::symlink;
+
+		let (dir, public) = setup("symlinks");
+		symlink(dir.join("secret.txt"), public.join("secret.txt")).unwrap();
+		symlink(public.join("js"), public.join("scripts")).unwrap();
+		let server = StaticFileServer::new("static", public.clone());
+
+		let res = get(&server, "/static/secret.txt").unwrap_err();
+		assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+		let res = get(&server, "/static/scripts/app.js").unwrap_err();
+		assert_eq!(res.status(), StatusCode::OK);
+
+		let server = server.follow_symlinks(false);
+		let res = get(&server, "/static/scripts/app.js").unwrap_err();
+		assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -0,0 +1,55 @@
+//! Parsing of the `Accept` family of headers.
+
+/// Splits an `Accept`-style header value into its lowercased items and their
+/// q-values, items without a `q` parameter have a quality of 1. Empty items
+/// are skipped, so an empty header yields no items at all.
+pub(crate) fn qualities(header: &str) -> Vec<(String, f32)> {
+	header
+		.split(',')
+		.filter_map(|item| {
+			let mut parts = item.split(';');
+			let value = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+			if value.is_empty() {
+				return None;
+			}
+			let quality = parts
+				.filter_map(|param| {
+					let mut pair = param.splitn(2, '=');
+					match (pair.next(), pair.next()) {
+						(Some(key), Some(value)) if key.trim() == "q" => {
+							value.trim().parse::<f32>().ok()
+						}
+						_ => None,
+					}
+				})
+				.next()
+				.unwrap_or(1.0);
+			Some((value, quality))
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn it_parses_items_and_q_values() {
+		assert_eq!(
+			qualities("Text/HTML, application/json;q=0.5 , */*; charset=utf-8; q=0"),
+			vec![
+				("text/html".to_string(), 1.0),
+				("application/json".to_string(), 0.5),
+				("*/*".to_string(), 0.0),
+			]
+		);
+		assert_eq!(qualities("gzip;q=oops"), vec![("gzip".to_string(), 1.0)]);
+	}
+
+	#[test]
+	fn it_skips_empty_items() {
+		assert!(qualities("").is_empty());
+		assert!(qualities(" , ").is_empty());
+		assert_eq!(qualities("br,,"), vec![("br".to_string(), 1.0)]);
+	}
+}
@@ -0,0 +1,329 @@
+//! A serde `Deserializer` over string key/value pairs, such as route params or
+//! form fields. Values are parsed into whatever type serde asks for, so a
+//! `"42"` can be deserialized into a `u32` field.
+use serde::de::{
+	self,
+	value::StrDeserializer,
+	DeserializeOwned,
+	DeserializeSeed,
+	Deserializer,
+	IntoDeserializer,
+	MapAccess,
+	SeqAccess,
+	Unexpected,
+	Visitor,
+};
+use std::{error, fmt, vec};
+
+/// Parses the value with `FromStr` and hands it to the matching visitor method.
+macro_rules! parse_value {
+	($($method:ident => $visit:ident,)*) => {$(
+		fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+			match self.0.parse() {
+				Ok(value) => visitor.$visit(value),
+				Err(_) => Err(de::Error::invalid_value(Unexpected::Str(self.0), &visitor)),
+			}
+		}
+	)*};
+}
+
+/// Forwards to the `ValueDeserializer`, if there's exactly one value.
+macro_rules! forward_single {
+	($($method:ident)*) => {$(
+		fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+			self.single()?.$method(visitor)
+		}
+	)*};
+}
+
+/// The error returned when the pairs couldn't be deserialized into the
+/// requested type.
+#[derive(Debug)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(&self.0)
+	}
+}
+
+impl error::Error for Error {
+	fn description(&self) -> &str {
+		&self.0
+	}
+}
+
+impl de::Error for Error {
+	fn custom<T: fmt::Display>(msg: T) -> Self {
+		Error(msg.to_string())
+	}
+}
+
+/// Deserializes `T` from key/value pairs, a key may have more than one value,
+/// in which case it can only be deserialized into a sequence.
+pub(crate) fn from_pairs<'a, T, I>(pairs: I) -> Result<T, Error>
+where
+	T: DeserializeOwned,
+	I: IntoIterator<Item = (&'a str, Vec<&'a str>)>,
+{
+	T::deserialize(PairsDeserializer {
+		iter: pairs.into_iter().collect::<Vec<_>>().into_iter(),
+		value: None,
+	})
+}
+
+struct PairsDeserializer<'a> {
+	iter: vec::IntoIter<(&'a str, Vec<&'a str>)>,
+	value: Option<Vec<&'a str>>,
+}
+
+impl<'de, 'a> Deserializer<'de> for PairsDeserializer<'a> {
+	type Error = Error;
+
+	fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_map(self)
+	}
+
+	forward_to_deserialize_any! {
+		bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
+		byte_buf option unit unit_struct newtype_struct seq tuple
+		tuple_struct map struct enum identifier ignored_any
+	}
+}
+
+impl<'de, 'a> MapAccess<'de> for PairsDeserializer<'a> {
+	type Error = Error;
+
+	fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+	where
+		K: DeserializeSeed<'de>,
+	{
+		match self.iter.next() {
+			Some((key, value)) => {
+				self.value = Some(value);
+				seed.deserialize(key.into_deserializer()).map(Some)
+			}
+			None => Ok(None),
+		}
+	}
+
+	fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
+	where
+		V: DeserializeSeed<'de>,
+	{
+		match self.value.take() {
+			Some(values) => seed.deserialize(ValuesDeserializer(values)),
+			None => Err(de::Error::custom("value is missing")),
+		}
+	}
+
+	fn size_hint(&self) -> Option<usize> {
+		Some(self.iter.len())
+	}
+}
+
+/// All the values supplied for a single key.
+struct ValuesDeserializer<'a>(Vec<&'a str>);
+
+impl<'a> ValuesDeserializer<'a> {
+	fn single(self) -> Result<ValueDeserializer<'a>, Error> {
+		match self.0.len() {
+			1 => Ok(ValueDeserializer(self.0[0])),
+			n => Err(de::Error::custom(format!("expected a single value, found {}", n))),
+		}
+	}
+}
+
+impl<'de, 'a> Deserializer<'de> for ValuesDeserializer<'a> {
+	type Error = Error;
+
+	fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		if self.0.len() == 1 {
+			return ValueDeserializer(self.0[0]).deserialize_any(visitor);
+		}
+
+		self.deserialize_seq(visitor)
+	}
+
+	fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_seq(SeqDeserializer(self.0.into_iter()))
+	}
+
+	fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+		self.deserialize_seq(visitor)
+	}
+
+	fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		self.single()?.deserialize_option(visitor)
+	}
+
+	fn deserialize_newtype_struct<V: Visitor<'de>>(
+		self,
+		_name: &'static str,
+		visitor: V,
+	) -> Result<V::Value, Error> {
+		visitor.visit_newtype_struct(self)
+	}
+
+	fn deserialize_enum<V: Visitor<'de>>(
+		self,
+		name: &'static str,
+		variants: &'static [&'static str],
+		visitor: V,
+	) -> Result<V::Value, Error> {
+		self.single()?.deserialize_enum(name, variants, visitor)
+	}
+
+	forward_single! {
+		deserialize_bool
+		deserialize_i8
+		deserialize_i16
+		deserialize_i32
+		deserialize_i64
+		deserialize_u8
+		deserialize_u16
+		deserialize_u32
+		deserialize_u64
+		deserialize_f32
+		deserialize_f64
+		deserialize_char
+		deserialize_str
+		deserialize_string
+		deserialize_bytes
+		deserialize_byte_buf
+		deserialize_unit
+		deserialize_identifier
+	}
+
+	forward_to_deserialize_any! {
+		unit_struct tuple_struct map struct ignored_any
+	}
+}
+
+struct SeqDeserializer<'a>(vec::IntoIter<&'a str>);
+
+impl<'de, 'a> SeqAccess<'de> for SeqDeserializer<'a> {
+	type Error = Error;
+
+	fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+	where
+		T: DeserializeSeed<'de>,
+	{
+		match self.0.next() {
+			Some(value) => seed.deserialize(ValueDeserializer(value)).map(Some),
+			None => Ok(None),
+		}
+	}
+
+	fn size_hint(&self) -> Option<usize> {
+		Some(self.0.len())
+	}
+}
+
+/// A single value, parsed on demand.
+struct ValueDeserializer<'a>(&'a str);
+
+impl<'de, 'a> Deserializer<'de> for ValueDeserializer<'a> {
+	type Error = Error;
+
+	fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_str(self.0)
+	}
+
+	fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		if self.0.is_empty() {
+			visitor.visit_none()
+		} else {
+			visitor.visit_some(self)
+		}
+	}
+
+	fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_unit()
+	}
+
+	fn deserialize_newtype_struct<V: Visitor<'de>>(
+		self,
+		_name: &'static str,
+		visitor: V,
+	) -> Result<V::Value, Error> {
+		visitor.visit_newtype_struct(self)
+	}
+
+	fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_seq(SeqDeserializer(vec![self.0].into_iter()))
+	}
+
+	fn deserialize_enum<V: Visitor<'de>>(
+		self,
+		_name: &'static str,
+		_variants: &'static [&'static str],
+		visitor: V,
+	) -> Result<V::Value, Error> {
+		let variant: StrDeserializer<Error> = self.0.into_deserializer();
+		visitor.visit_enum(variant)
+	}
+
+	fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_bytes(self.0.as_bytes())
+	}
+
+	fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		self.deserialize_bytes(visitor)
+	}
+
+	parse_value! {
+		deserialize_bool => visit_bool,
+		deserialize_i8 => visit_i8,
+		deserialize_i16 => visit_i16,
+		deserialize_i32 => visit_i32,
+		deserialize_i64 => visit_i64,
+		deserialize_u8 => visit_u8,
+		deserialize_u16 => visit_u16,
+		deserialize_u32 => visit_u32,
+		deserialize_u64 => visit_u64,
+		deserialize_f32 => visit_f32,
+		deserialize_f64 => visit_f64,
+		deserialize_char => visit_char,
+	}
+
+	forward_to_deserialize_any! {
+		str string unit_struct tuple tuple_struct map struct identifier ignored_any
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::HashMap;
+
+	#[test]
+	fn it_parses_values_into_the_requested_type() {
+		let pairs = vec![("id", vec!["42"]), ("page", vec!["3"])];
+		let map: HashMap<String, u32> = from_pairs(pairs).unwrap();
+
+		assert_eq!(map["id"], 42);
+		assert_eq!(map["page"], 3);
+	}
+
+	#[test]
+	fn it_fails_on_invalid_values() {
+		let pairs = vec![("id", vec!["seun"])];
+		let result = from_pairs::<HashMap<String, u32>, _>(pairs);
+
+		assert!(result.is_err());
+	}
+
+	#[test]
+	fn it_deserializes_repeated_values_into_a_sequence() {
+		let pairs = vec![("tags", vec!["1", "2"])];
+		let map: HashMap<String, Vec<u8>> = from_pairs(pairs).unwrap();
+
+		assert_eq!(map["tags"], vec![1, 2]);
+
+		let pairs = vec![("tags", vec!["1", "2"])];
+		let result = from_pairs::<HashMap<String, u8>, _>(pairs);
+
+		assert!(result.is_err());
+	}
+}
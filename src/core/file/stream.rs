@@ -1,5 +1,6 @@
 use futures::prelude::*;
 use hyper::Chunk;
+use std::{cmp, collections::VecDeque, io::SeekFrom};
 use tokio::{
 	fs::File,
 	io::{AsyncRead, Error},
@@ -10,17 +11,70 @@ use tokio::{
 struct FileStream {
 	file: File,
 	buf: [u8; 4096],
+	/// the offset to seek to, before the first read.
+	offset: Option<u64>,
+	/// the number of bytes left to read, `None` reads to the end of the file.
+	remaining: Option<u64>,
 }
 
 pub fn stream(file: File) -> impl Stream<Item = Chunk, Error = Error> {
-	FileStream::new(file)
+	FileStream::new(file, None, None)
+}
+
+/// Streams `length` bytes of the file, starting at `offset`.
+pub fn stream_range(
+	file: File,
+	offset: u64,
+	length: u64,
+) -> impl Stream<Item = Chunk, Error = Error> {
+	FileStream::new(file, Some(offset), Some(length))
+}
+
+/// Streams several ranges of the file through the same handle, every range is
+/// preceded by its head chunk, e.g a multipart boundary. The parts are
+/// `(head, offset, length)`.
+pub fn stream_ranges(
+	file: File,
+	parts: Vec<(Chunk, u64, u64)>,
+) -> impl Stream<Item = Chunk, Error = Error> {
+	FileRanges {
+		stream: FileStream::new(file, None, Some(0)),
+		parts: parts.into_iter().collect(),
+	}
+}
+
+struct FileRanges {
+	stream: FileStream,
+	parts: VecDeque<(Chunk, u64, u64)>,
+}
+
+impl Stream for FileRanges {
+	type Item = Chunk;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		if let Some(chunk) = try_ready!(self.stream.poll()) {
+			return Ok(Async::Ready(Some(chunk)));
+		}
+
+		match self.parts.pop_front() {
+			Some((head, offset, length)) => {
+				self.stream.offset = Some(offset);
+				self.stream.remaining = Some(length);
+				Ok(Async::Ready(Some(head)))
+			}
+			None => Ok(Async::Ready(None)),
+		}
+	}
 }
 
 impl FileStream {
-	fn new(file: File) -> Self {
+	fn new(file: File, offset: Option<u64>, remaining: Option<u64>) -> Self {
 		Self {
 			file,
 			buf: [0u8; 4096],
+			offset,
+			remaining,
 		}
 	}
 }
@@ -30,9 +84,23 @@ impl Stream for FileStream {
 	type Error = Error;
 
 	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-		let n_bytes = try_ready!(self.file.poll_read(&mut self.buf));
+		if let Some(offset) = self.offset {
+			try_ready!(self.file.poll_seek(SeekFrom::Start(offset)));
+			self.offset = None;
+		}
+
+		let max = match self.remaining {
+			Some(0) => return Ok(Async::Ready(None)),
+			Some(remaining) => cmp::min(remaining, self.buf.len() as u64) as usize,
+			None => self.buf.len(),
+		};
+
+		let n_bytes = try_ready!(self.file.poll_read(&mut self.buf[..max]));
 		if n_bytes > 0 {
-			Ok(Async::Ready(Some(Chunk::from(self.buf.to_vec()))))
+			if let Some(ref mut remaining) = self.remaining {
+				*remaining -= n_bytes as u64;
+			}
+			Ok(Async::Ready(Some(Chunk::from(self.buf[..n_bytes].to_vec()))))
 		} else {
 			Ok(Async::Ready(None))
 		}
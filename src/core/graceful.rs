@@ -0,0 +1,168 @@
+use super::rootservice::RootService;
+use futures::{future::Shared, prelude::*, sync::mpsc::Sender};
+use hyper::{self, server::conn::Connection};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// The shutdown signal shared between the accept loop and every connection.
+pub(crate) type Signal = Shared<Box<Future<Item = (), Error = ()> + Send>>;
+
+pub(crate) fn signal<F>(future: F) -> Signal
+where
+	F: Future<Item = (), Error = ()> + Send + 'static,
+{
+	(Box::new(future) as Box<Future<Item = (), Error = ()> + Send>).shared()
+}
+
+/// Wraps a hyper `Connection`, once the shutdown signal resolves, the
+/// connection is told to finish the request it is currently serving and then
+/// close, instead of waiting for the next keep-alive request.
+/// If the `force` signal resolves, the connection is dropped right away.
+///
+/// It also holds on to a clone of the drain `Sender`, the server knows all
+/// connections are done when every `Sender` has been dropped.
+pub(crate) struct GracefulConnection<I> {
+	conn: Connection<I, RootService>,
+	signal: Option<Signal>,
+	force: Signal,
+	_drain: Sender<()>,
+}
+
+impl<I> GracefulConnection<I> {
+	pub(crate) fn new(
+		conn: Connection<I, RootService>,
+		signal: Signal,
+		force: Signal,
+		drain: Sender<()>,
+	) -> Self {
+		Self {
+			conn,
+			signal: Some(signal),
+			force,
+			_drain: drain,
+		}
+	}
+}
+
+impl<I> Future for GracefulConnection<I>
+where
+	I: AsyncRead + AsyncWrite + Send + 'static,
+{
+	type Item = ();
+	type Error = hyper::Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		match self.force.poll() {
+			Ok(Async::NotReady) => {}
+			_ => return Ok(Async::Ready(())),
+		};
+
+		let shutdown = match self.signal {
+			Some(ref mut signal) => {
+				match signal.poll() {
+					Ok(Async::NotReady) => false,
+					// an errored signal is still a signal to shutdown.
+					_ => true,
+				}
+			}
+			None => false,
+		};
+
+		if shutdown {
+			self.signal = None;
+			self.conn.graceful_shutdown();
+		}
+
+		self.conn.poll()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use core::{ArcReactor, Request, Response};
+	use futures::{future, sync::oneshot, Future};
+	use proto::FutureResponse;
+	use std::{
+		io::{ErrorKind, Read, Write},
+		net::{TcpListener, TcpStream},
+		sync::mpsc,
+		thread,
+		time::{Duration, Instant},
+	};
+	use tokio::{runtime::Runtime, timer::Delay};
+
+	fn SlowService(_req: Request, res: Response) -> FutureResponse {
+		let delay = Delay::new(Instant::now() + Duration::from_millis(200));
+		Box::new(delay.then(move |_| Ok(res.with_text("done"))))
+	}
+
+	fn StuckService(_req: Request, _res: Response) -> FutureResponse {
+		Box::new(future::empty())
+	}
+
+	/// Starts the server on a runtime and sends it a request, the returned
+	/// receiver fires once the server future resolves.
+	fn serve(reactor: ArcReactor) -> (Runtime, TcpStream, oneshot::Sender<()>, mpsc::Receiver<()>) {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let addr = listener.local_addr().unwrap();
+		let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+		let (done_tx, done_rx) = mpsc::channel();
+
+		let server = reactor
+			.listener(listener)
+			.shutdown_signal(shutdown_rx.map_err(|_| ()))
+			.start()
+			.unwrap();
+		let mut runtime = Runtime::new().unwrap();
+		runtime.spawn(server.map(move |_| {
+			let _ = done_tx.send(());
+		}));
+
+		let mut stream = TcpStream::connect(addr).unwrap();
+		stream
+			.set_read_timeout(Some(Duration::from_secs(5)))
+			.unwrap();
+		stream
+			.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+			.unwrap();
+		// let the server start serving the request.
+		thread::sleep(Duration::from_millis(50));
+
+		(runtime, stream, shutdown_tx, done_rx)
+	}
+
+	#[test]
+	fn it_finishes_in_flight_requests_on_shutdown() {
+		let reactor = ArcReactor::new().service(SlowService);
+		let (_runtime, mut stream, shutdown, done) = serve(reactor);
+
+		shutdown.send(()).unwrap();
+
+		// the response is sent, then the connection is closed.
+		let mut response = String::new();
+		stream.read_to_string(&mut response).unwrap();
+		assert!(response.starts_with("HTTP/1.1 200 OK"));
+		assert!(response.ends_with("done"));
+
+		done.recv_timeout(Duration::from_secs(5)).unwrap();
+	}
+
+	#[test]
+	fn it_closes_connections_that_outlive_the_shutdown_timeout() {
+		let reactor = ArcReactor::new()
+			.service(StuckService)
+			.shutdown_timeout(Duration::from_millis(100));
+		let (_runtime, mut stream, shutdown, done) = serve(reactor);
+
+		let started = Instant::now();
+		shutdown.send(()).unwrap();
+		done.recv_timeout(Duration::from_secs(5)).unwrap();
+		assert!(started.elapsed() >= Duration::from_millis(100));
+
+		// the connection is closed without a response.
+		let mut response = Vec::new();
+		match stream.read_to_end(&mut response) {
+			Ok(_) => assert!(response.is_empty()),
+			Err(err) => assert_eq!(err.kind(), ErrorKind::ConnectionReset),
+		}
+	}
+}
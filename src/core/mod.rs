@@ -1,6 +1,14 @@
+mod accept;
+mod de;
 pub mod file;
+mod graceful;
+mod range;
 mod reactor;
 mod request;
 mod response;
 mod rootservice;
-pub use self::{reactor::*, request::*, response::*};
+mod validators;
+pub(crate) use self::{accept::qualities, de::from_pairs};
+pub use self::{de::Error as DeError, reactor::*, request::*, response::*};
+#[cfg(unix)]
+pub use tokio_uds::UCred;
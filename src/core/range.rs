@@ -0,0 +1,212 @@
+//! Parses the `Range` header of a request for a representation of a known
+//! size.
+use hyperx::header::HttpDate;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// More ranges than this and the header is ignored, so a client can't make
+/// us serve thousands of tiny parts.
+const MAX_RANGES: usize = 32;
+
+/// An inclusive range of bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ByteRange {
+	pub start: u64,
+	pub end: u64,
+}
+
+impl ByteRange {
+	pub fn len(&self) -> u64 {
+		self.end - self.start + 1
+	}
+
+	/// The value of the `Content-Range` header for this range.
+	pub fn content_range(&self, size: u64) -> String {
+		format!("bytes {}-{}/{}", self.start, self.end, size)
+	}
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Ranges {
+	/// The header is missing, invalid or uses a unit other than bytes,
+	/// the full representation should be served.
+	Full,
+	Satisfiable(Vec<ByteRange>),
+	/// None of the ranges overlap the representation.
+	Unsatisfiable,
+}
+
+/// Parses the header against a representation of `size` bytes.
+///
+/// Overlapping and adjacent ranges are merged and returned in ascending
+/// order, so a client can't make us send the same bytes more than once
+/// (RFC 7233 §6.1).
+pub(crate) fn parse(header: &str, size: u64) -> Ranges {
+	let header = header.trim();
+	if !header.starts_with("bytes=") {
+		return Ranges::Full;
+	}
+
+	let mut ranges = Vec::new();
+	for spec in header["bytes=".len()..].split(',') {
+		let spec = spec.trim();
+		if spec.is_empty() {
+			continue;
+		}
+
+		let mut bounds = spec.splitn(2, '-');
+		let (start, end) = match (bounds.next(), bounds.next()) {
+			(Some(start), Some(end)) => (start.trim(), end.trim()),
+			_ => return Ranges::Full,
+		};
+
+		let range = if start.is_empty() {
+			// a suffix range, the last n bytes.
+			let length = match end.parse::<u64>() {
+				Ok(length) => length,
+				Err(_) => return Ranges::Full,
+			};
+			if length == 0 || size == 0 {
+				continue;
+			}
+			ByteRange {
+				start: size.saturating_sub(length),
+				end: size - 1,
+			}
+		} else {
+			let start = match start.parse::<u64>() {
+				Ok(start) => start,
+				Err(_) => return Ranges::Full,
+			};
+			let end = if end.is_empty() {
+				None
+			} else {
+				match end.parse::<u64>() {
+					Ok(end) if end >= start => Some(end),
+					_ => return Ranges::Full,
+				}
+			};
+			if start >= size {
+				continue;
+			}
+			ByteRange {
+				start,
+				end: end.map_or(size - 1, |end| end.min(size - 1)),
+			}
+		};
+
+		ranges.push(range);
+	}
+
+	if ranges.len() > MAX_RANGES {
+		return Ranges::Full;
+	}
+
+	if ranges.is_empty() {
+		Ranges::Unsatisfiable
+	} else {
+		Ranges::Satisfiable(coalesce(ranges))
+	}
+}
+
+/// Merges the overlapping and adjacent ranges.
+fn coalesce(mut ranges: Vec<ByteRange>) -> Vec<ByteRange> {
+	ranges.sort_by_key(|range| range.start);
+
+	let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+	for range in ranges {
+		if let Some(last) = merged.last_mut() {
+			if range.start <= last.end + 1 {
+				last.end = last.end.max(range.end);
+				continue;
+			}
+		}
+		merged.push(range);
+	}
+
+	merged
+}
+
+/// Returns true if the `Range` header should be honoured given the request's
+/// `If-Range` header and the modification time of the file.
+///
+/// Only dates are compared, files don't get a strong entity tag, and weak ones
+/// never match an `If-Range`.
+pub(crate) fn if_range_matches(if_range: Option<&str>, modified: Option<SystemTime>) -> bool {
+	let if_range = match if_range {
+		Some(if_range) => if_range,
+		None => return true,
+	};
+
+	let seconds = |time: SystemTime| time.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs());
+
+	match (if_range.parse::<HttpDate>(), modified) {
+		(Ok(date), Some(modified)) => {
+			let date = seconds(SystemTime::from(date));
+			date.is_some() && date == seconds(modified)
+		}
+		_ => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn it_parses_ranges() {
+		assert_eq!(
+			parse("bytes=0-99, 500-, -200", 1000),
+			Ranges::Satisfiable(vec![
+				ByteRange { start: 0, end: 99 },
+				ByteRange {
+					start: 500,
+					end: 999,
+				},
+			])
+		);
+		assert_eq!(
+			parse("bytes=900-2000", 1000),
+			Ranges::Satisfiable(vec![ByteRange {
+				start: 900,
+				end: 999,
+			}])
+		);
+		assert_eq!(
+			parse("bytes=-2000", 1000),
+			Ranges::Satisfiable(vec![ByteRange { start: 0, end: 999 }])
+		);
+	}
+
+	#[test]
+	fn it_coalesces_overlapping_and_adjacent_ranges() {
+		assert_eq!(
+			parse("bytes=0-,0-,0-,0-", 1000),
+			Ranges::Satisfiable(vec![ByteRange { start: 0, end: 999 }])
+		);
+		assert_eq!(
+			parse("bytes=500-599, 0-99, 100-199, 50-149", 1000),
+			Ranges::Satisfiable(vec![
+				ByteRange { start: 0, end: 199 },
+				ByteRange {
+					start: 500,
+					end: 599,
+				},
+			])
+		);
+	}
+
+	#[test]
+	fn it_ignores_invalid_headers() {
+		assert_eq!(parse("items=0-10", 1000), Ranges::Full);
+		assert_eq!(parse("bytes=10-5", 1000), Ranges::Full);
+		assert_eq!(parse("bytes=a-b", 1000), Ranges::Full);
+		assert_eq!(parse("bytes=10", 1000), Ranges::Full);
+	}
+
+	#[test]
+	fn it_detects_unsatisfiable_ranges() {
+		assert_eq!(parse("bytes=1000-", 1000), Ranges::Unsatisfiable);
+		assert_eq!(parse("bytes=-0", 1000), Ranges::Unsatisfiable);
+		assert_eq!(parse("bytes=0-10", 0), Ranges::Unsatisfiable);
+	}
+}
@@ -1,13 +1,42 @@
-use super::{rootservice::RootService, Request, Response};
-use contrib::BodyParser;
-use futures::{Future, Stream};
+use super::{
+	graceful::{self, GracefulConnection},
+	rootservice::RootService,
+	Request,
+	Response,
+};
+use contrib::{BeforeRouting, BodyLimit, BodyParser};
+use futures::{
+	future,
+	stream,
+	sync::{mpsc, oneshot},
+	Future,
+	Stream,
+};
 use hyper::server::conn::Http;
 use native_tls::TlsAcceptor;
 use proto::{ArcHandler, ArcService, MiddleWare};
 use routing::Router;
-use std::{io, sync::Arc};
-use tokio::{self, net::TcpListener};
+#[cfg(unix)]
+use std::{
+	fs,
+	os::unix::{fs::FileTypeExt, net::UnixStream as StdUnixStream},
+	path::{Path, PathBuf},
+};
+use std::{
+	io,
+	net::{self, SocketAddr},
+	sync::Arc,
+	time::{Duration, Instant},
+};
+use tokio::{
+	self,
+	net::{TcpListener, TcpStream},
+	reactor::Handle,
+	timer::Delay,
+};
 use tokio_tls::TlsAcceptorExt;
+#[cfg(unix)]
+use tokio_uds::{UnixListener, UnixStream};
 
 /// The main server, the ArcReactor is where you mount your routes, middlewares
 /// and initiate the server.
@@ -27,22 +56,56 @@ use tokio_tls::TlsAcceptorExt;
 /// 	tokio::run(server);
 /// }
 /// ```
+///
+/// The same server can also listen on several addresses at once.
+///
+/// ```rust,no_run
+/// extern crate arc_reactor;
+/// extern crate tokio;
+/// use arc_reactor::core::ArcReactor;
+///
+/// fn main() {
+/// 	let server = ArcReactor::default()
+/// 		.bind(([127, 0, 0, 1], 8080).into())
+/// 		.bind("[::1]:8080".parse().unwrap())
+/// 		.start()
+/// 		.expect("couldn't start server");
+/// 	tokio::run(server);
+/// }
+/// ```
 pub struct ArcReactor {
-	port: i16,
+	port: u16,
+	addrs: Vec<SocketAddr>,
+	listeners: Vec<net::TcpListener>,
+	#[cfg(unix)]
+	unix_paths: Vec<PathBuf>,
 	arc_handler: ArcHandler,
 	tls_acceptor: Option<Arc<TlsAcceptor>>,
+	shutdown_signal: Option<Box<Future<Item = (), Error = ()> + Send>>,
+	shutdown_timeout: Duration,
+	body_limit: Option<u64>,
+	/// the handler is a `Router`, so the body can be read after routing.
+	routed: bool,
 }
 
 impl Default for ArcReactor {
 	fn default() -> Self {
 		ArcReactor {
 			port: 8080,
+			addrs: Vec::new(),
+			listeners: Vec::new(),
+			#[cfg(unix)]
+			unix_paths: Vec::new(),
 			arc_handler: ArcHandler {
 				before: Some(mw![BodyParser]),
 				after: None,
 				handler: None,
 			},
 			tls_acceptor: None,
+			shutdown_signal: None,
+			shutdown_timeout: Duration::from_secs(30),
+			body_limit: None,
+			routed: false,
 		}
 	}
 }
@@ -55,12 +118,45 @@ impl ArcReactor {
 	}
 
 	/// Sets the port for the server to listen on and returns the instance.
-	pub fn port(mut self, port: i16) -> Self {
+	///
+	/// The server binds to `0.0.0.0` on this port, unless an address or
+	/// listener has been supplied through `bind` or `listener`.
+	pub fn port(mut self, port: u16) -> Self {
 		self.port = port;
 
 		self
 	}
 
+	/// Adds an address for the server to listen on, this can be called
+	/// multiple times to listen on several addresses (e.g IPv4 and IPv6).
+	pub fn bind(mut self, addr: SocketAddr) -> Self {
+		self.addrs.push(addr);
+
+		self
+	}
+
+	/// Adds an already bound listener for the server to accept connections
+	/// from. e.g a socket handed over by systemd socket activation.
+	pub fn listener(mut self, listener: net::TcpListener) -> Self {
+		self.listeners.push(listener);
+
+		self
+	}
+
+	/// Adds a Unix domain socket for the server to listen on.
+	///
+	/// Connections accepted on a Unix socket are always served over plain
+	/// http, `Request::remote_ip` returns `None` for them and
+	/// `Request::peer_cred` returns the credentials of the connected process.
+	/// The socket file is removed once the server has shutdown, a stale one
+	/// left by a server that didn't exit cleanly is removed on `start`.
+	#[cfg(unix)]
+	pub fn unix<P: Into<PathBuf>>(mut self, path: P) -> Self {
+		self.unix_paths.push(path.into());
+
+		self
+	}
+
 	/// set the TlsAcceptor
 	/// check the `examples` folder for more info.
 	pub fn tls(mut self, acceptor: TlsAcceptor) -> Self {
@@ -73,6 +169,7 @@ impl ArcReactor {
 	pub fn routes(mut self, routes: Router) -> Self {
 		let routes = Box::new(routes) as Box<ArcService>;
 		self.arc_handler.handler = Some(routes);
+		self.routed = true;
 
 		self
 	}
@@ -80,6 +177,60 @@ impl ArcReactor {
 	pub fn service<S: ArcService + 'static>(mut self, service: S) -> Self {
 		let service = Box::new(service) as Box<ArcService>;
 		self.arc_handler.handler = Some(service);
+		self.routed = false;
+
+		self
+	}
+
+	/// Sets a future that signals the server to shutdown gracefully.
+	///
+	/// Once it resolves (or errors), the server stops accepting new
+	/// connections, lets the requests already in-flight finish and then
+	/// resolves the future returned by `start`.
+	///
+	/// ```rust,no_run
+	/// extern crate arc_reactor;
+	/// extern crate futures;
+	/// extern crate tokio;
+	/// use arc_reactor::core::ArcReactor;
+	/// use futures::sync::oneshot;
+	///
+	/// fn main() {
+	/// 	let (tx, rx) = oneshot::channel::<()>();
+	/// 	let server = ArcReactor::default()
+	/// 		.shutdown_signal(rx.map_err(|_| ()))
+	/// 		.start()
+	/// 		.expect("couldn't start server");
+	///
+	/// 	// call `tx.send(())` from anywhere to stop the server.
+	/// 	tokio::run(server);
+	/// }
+	/// ```
+	pub fn shutdown_signal<F>(mut self, signal: F) -> Self
+	where
+		F: Future<Item = (), Error = ()> + Send + 'static,
+	{
+		self.shutdown_signal = Some(Box::new(signal));
+
+		self
+	}
+
+	/// Sets how long the server waits for in-flight connections to finish
+	/// after the shutdown signal, before it resolves anyway.
+	///
+	/// Defaults to 30 seconds.
+	pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+		self.shutdown_timeout = timeout;
+
+		self
+	}
+
+	/// The most bytes the `BodyParser` buffers for any request, see
+	/// `BodyLimit`.
+	///
+	/// There's no limit by default.
+	pub fn body_limit(mut self, bytes: u64) -> Self {
+		self.body_limit = Some(bytes);
 
 		self
 	}
@@ -105,6 +256,10 @@ impl ArcReactor {
 	/// Binds the listener and returns a future representing the server
 	/// this future should be spawned on the tokio runtime.
 	///
+	/// If a `shutdown_signal` was supplied, the future resolves once the
+	/// signal has fired and all connections have been drained, or the
+	/// `shutdown_timeout` has elapsed.
+	///
 	/// # Panics
 	///
 	/// Calling this function will panic if: no routes are supplied, or it
@@ -113,24 +268,131 @@ impl ArcReactor {
 	pub fn start(self) -> Result<impl Future<Item = (), Error = ()> + Send, io::Error> {
 		let ArcReactor {
 			port,
-			arc_handler,
+			mut addrs,
+			listeners: std_listeners,
+			#[cfg(unix)]
+			unix_paths,
+			mut arc_handler,
 			tls_acceptor: acceptor,
+			shutdown_signal,
+			shutdown_timeout,
+			body_limit,
+			routed,
 		} = self;
 
-		let addr = format!("0.0.0.0:{}", port).parse().unwrap();
+		// these have to be set before the `BodyParser` runs.
+		let mut first: Vec<Box<MiddleWare<Request>>> = Vec::new();
+		if routed {
+			first.push(Box::new(BeforeRouting));
+		}
+		if let Some(limit) = body_limit {
+			first.push(Box::new(BodyLimit(limit)));
+		}
+		if !first.is_empty() {
+			if let Some(before) = arc_handler.before.take() {
+				first.push(before);
+			}
+			arc_handler.before = Some(Box::new(first));
+		}
+
+		#[cfg(unix)]
+		let has_unix = !unix_paths.is_empty();
+		#[cfg(not(unix))]
+		let has_unix = false;
+
+		if addrs.is_empty() && std_listeners.is_empty() && !has_unix {
+			addrs.push(SocketAddr::from(([0, 0, 0, 0], port)));
+		}
 
-		info!("Binding to port {}", port);
+		let mut listeners = Vec::with_capacity(addrs.len() + std_listeners.len());
+		for addr in addrs {
+			listeners.push(TcpListener::bind(&addr)?);
+		}
+		for listener in std_listeners {
+			listeners.push(TcpListener::from_std(listener, &Handle::default())?);
+		}
+
+		let incoming = listeners.into_iter().fold(
+			Box::new(stream::empty()) as Box<Stream<Item = TcpStream, Error = io::Error> + Send>,
+			|incoming, listener| {
+				if let Ok(addr) = listener.local_addr() {
+					info!("Listening on {}", addr);
+				}
+				Box::new(incoming.select(listener.incoming()))
+			},
+		);
 
-		let listener = TcpListener::bind(&addr)?;
+		#[cfg(unix)]
+		let mut unix_listeners = Vec::with_capacity(unix_paths.len());
+		#[cfg(unix)]
+		for path in &unix_paths {
+			remove_stale_socket(path)?;
+			unix_listeners.push(UnixListener::bind(path)?);
+			info!("Listening on {}", path.display());
+		}
 
 		let http = Http::new();
 
-		let conn_stream_future = listener
-			.incoming()
+		let signal = match shutdown_signal {
+			Some(signal) => graceful::signal(signal),
+			None => graceful::signal(future::empty()),
+		};
+		let connection_signal = signal.clone();
+
+		// fired once the `shutdown_timeout` elapses, to close connections that are
+		// still open.
+		let (force_tx, force_rx) = oneshot::channel::<()>();
+		let force = graceful::signal(force_rx.map_err(|_| ()));
+
+		// every connection holds a clone of `drain_tx`, once they have all been
+		// dropped, `drain_rx` ends.
+		let (drain_tx, drain_rx) = mpsc::channel::<()>(0);
+
+		#[cfg(unix)]
+		let unix_future = {
+			let arc_handler = arc_handler.clone();
+			let http = http.clone();
+			let connection_signal = connection_signal.clone();
+			let force = force.clone();
+			let drain_tx = drain_tx.clone();
+
+			unix_listeners
+				.into_iter()
+				.fold(
+					Box::new(stream::empty())
+						as Box<Stream<Item = UnixStream, Error = io::Error> + Send>,
+					|incoming, listener| Box::new(incoming.select(listener.incoming())),
+				)
+				.map_err(|err| error!("error accepting connection: {}", err))
+				.for_each(move |socket| {
+					let mut service = RootService::new(arc_handler.clone(), None);
+					service.peer_cred = socket.peer_cred().ok();
+					let conn = http.serve_connection(socket, service);
+					let connection_future = GracefulConnection::new(
+						conn,
+						connection_signal.clone(),
+						force.clone(),
+						drain_tx.clone(),
+					).map_err(|err| error!("serve_connection Error: {}", err));
+
+					tokio::spawn(connection_future);
+
+					Ok(())
+				})
+				// an accept error only stops this loop, tcp connections are still served.
+				.then(|_| Ok::<(), ()>(()))
+		};
+		#[cfg(not(unix))]
+		let unix_future = future::ok::<(), ()>(());
+
+		let conn_stream_future = incoming
 			.map_err(|err| error!("error accepting connection: {}", err))
 			.for_each(move |socket| {
 				let service = arc_handler.clone();
 				let remote_ip = socket.peer_addr().ok();
+				let signal = connection_signal.clone();
+				let force = force.clone();
+				let drain = drain_tx.clone();
 				// user has configured a tls acceptor
 				if let Some(ref acceptor) = acceptor {
 					let http_clone = http.clone();
@@ -139,8 +401,9 @@ impl ArcReactor {
 						.map_err(|err| error!("TLS Handshake Error: {}", err))
 						.and_then(move |socket| {
 							// handshake successful
-							http_clone
-								.serve_connection(socket, RootService { service, remote_ip })
+							let conn = http_clone
+								.serve_connection(socket, RootService::new(service, remote_ip));
+							GracefulConnection::new(conn, signal, force, drain)
 								.map_err(|err| error!("serve_connection Error: {}", err))
 								.and_then(|_| Ok(()))
 						});
@@ -148,8 +411,8 @@ impl ArcReactor {
 					tokio::spawn(connection_future);
 				} else {
 					// default to http
-					let connection_future = http
-						.serve_connection(socket, RootService { service, remote_ip })
+					let conn = http.serve_connection(socket, RootService::new(service, remote_ip));
+					let connection_future = GracefulConnection::new(conn, signal, force, drain)
 						.map_err(|err| error!("serve_connection Error: {}", err))
 						.and_then(|_| Ok(()));
 
@@ -157,8 +420,165 @@ impl ArcReactor {
 				}
 
 				Ok(())
+			})
+			.then(|_| Ok::<(), ()>(()));
+
+		let server = conn_stream_future
+			.join(unix_future)
+			.select2(signal)
+			.then(move |result| {
+				// drops the accept loops (and with it, the listeners and the last `drain_tx`)
+				// if the shutdown signal won the race.
+				drop(result);
+				info!("Shutting down, waiting for open connections to finish");
+
+				let drained = drain_rx.for_each(|_| Ok(()));
+				let deadline = Delay::new(Instant::now() + shutdown_timeout);
+
+				drained.select2(deadline).then(move |result| {
+					match result {
+						Ok(future::Either::B(_)) => {
+							warn!("Shutdown timeout elapsed, closing open connections");
+							let _ = force_tx.send(());
+						}
+						Err(future::Either::B((err, _))) => {
+							error!("Shutdown timer error: {}", err);
+							let _ = force_tx.send(());
+						}
+						_ => {}
+					};
+
+					#[cfg(unix)]
+					for path in unix_paths {
+						if let Err(err) = fs::remove_file(&path) {
+							error!("Error removing unix socket {}: {}", path.display(), err);
+						}
+					}
+
+					Ok(())
+				})
 			});
 
-		Ok(conn_stream_future)
+		Ok(server)
+	}
+}
+
+/// Binding a Unix socket fails if its file exists, which is the case after a
+/// server exits without cleaning up. The file is removed if it's a socket
+/// no one is listening on anymore.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+	let metadata = match fs::symlink_metadata(path) {
+		Ok(metadata) => metadata,
+		Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+		Err(err) => return Err(err),
+	};
+
+	if !metadata.file_type().is_socket() {
+		return Err(io::Error::new(
+			io::ErrorKind::AlreadyExists,
+			format!("{} exists and is not a unix socket", path.display()),
+		));
+	}
+
+	if StdUnixStream::connect(path).is_ok() {
+		return Err(io::Error::new(
+			io::ErrorKind::AddrInUse,
+			format!("{} is in use by another server", path.display()),
+		));
+	}
+
+	warn!("Removing stale unix socket {}", path.display());
+	fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	#[cfg(unix)]
+	use std::{
+		env,
+		os::unix::{fs::MetadataExt, net::UnixListener as StdUnixListener},
+		process,
+	};
+	use std::{
+		io::{Read, Write},
+		time::Duration,
+	};
+	use tokio::runtime::Runtime;
+
+	fn HelloService(_req: Request, res: Response) -> Response {
+		res.with_text("hello")
+	}
+
+	#[cfg(unix)]
+	fn CredService(req: Request, res: Response) -> Response {
+		let uid = req
+			.peer_cred()
+			.map(|cred| cred.uid.to_string())
+			.unwrap_or_default();
+		res.with_text(uid)
+	}
+
+	/// Sends a request over `stream` and returns the raw response.
+	fn get<S: Read + Write>(mut stream: S) -> String {
+		stream
+			.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+			.unwrap();
+		let mut response = String::new();
+		stream.read_to_string(&mut response).unwrap();
+		response
+	}
+
+	#[test]
+	fn it_serves_through_a_pre_bound_listener() {
+		let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let addr = listener.local_addr().unwrap();
+		let server = ArcReactor::new()
+			.service(HelloService)
+			.listener(listener)
+			.start()
+			.unwrap();
+		let mut runtime = Runtime::new().unwrap();
+		runtime.spawn(server);
+
+		let stream = net::TcpStream::connect(addr).unwrap();
+		stream
+			.set_read_timeout(Some(Duration::from_secs(5)))
+			.unwrap();
+		let response = get(stream);
+		assert!(response.starts_with("HTTP/1.1 200 OK"));
+		assert!(response.ends_with("hello"));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn it_serves_over_a_unix_socket() {
+		let dir = env::temp_dir().join(format!("arc-reactor-unix-{}", process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("server.sock");
+		// the socket file of a server that didn't exit cleanly.
+		drop(StdUnixListener::bind(&path).unwrap());
+
+		let server = ArcReactor::new()
+			.service(CredService)
+			.unix(path.clone())
+			.start()
+			.unwrap();
+		let mut runtime = Runtime::new().unwrap();
+		runtime.spawn(server);
+
+		let stream = StdUnixStream::connect(&path).unwrap();
+		stream
+			.set_read_timeout(Some(Duration::from_secs(5)))
+			.unwrap();
+		let response = get(stream);
+		assert!(response.starts_with("HTTP/1.1 200 OK"));
+		// the directory was created by this process, so it's owned by the same user.
+		let uid = fs::metadata(&dir).unwrap().uid();
+		assert!(response.ends_with(&uid.to_string()));
+
+		drop(runtime);
+		fs::remove_dir_all(dir).unwrap();
 	}
 }
@@ -1,9 +1,9 @@
-use contrib::Json;
-#[cfg(feature = "unstable")]
-use contrib::MultiPartMap;
+use super::de::{self, Error as DeError};
+use contrib::{Buffered, Decoders, Form, UrlEncoded};
+use erased_serde;
 use http::request::Parts;
 use hyper::{
-	header::{HeaderMap, HeaderValue},
+	header::{HeaderMap, HeaderValue, CONTENT_TYPE},
 	Body,
 	Method,
 	Uri,
@@ -14,9 +14,9 @@ use routing::recognizer::Params;
 use serde::de::DeserializeOwned;
 use serde_json::{self, from_slice};
 use serde_qs::{self, from_str};
-#[cfg(feature = "unstable")]
-use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::{error, fmt, net::SocketAddr};
+#[cfg(unix)]
+use tokio_uds::UCred;
 
 /// The Request Struct, This is passed to Middlewares and route handlers.
 ///
@@ -93,6 +93,115 @@ pub enum QueryParseError {
 	None,
 }
 
+/// The error returned by `Request::params_as()`.
+///
+/// `From<ParamsError>` is implemented for Response
+/// so you can use the `?` to unwrap or return an early response
+///
+/// ```rust, ignore
+/// #[derive(Deserialize)]
+/// struct Profile {
+/// 	id: u64,
+/// }
+///
+/// #[service]
+/// fn ProfileService(req: Request, res: Response) {
+/// 	let Profile { id } = req.params_as()?;
+/// 	// will return an error response with the
+/// 	// json '{ "error": "route params were empty" }' if ParamsError::None
+/// 	// or '{ "error": "{parse error}" }' if it failed to deserialize.
+/// }
+/// ```
+///
+#[derive(Debug)]
+pub enum ParamsError {
+	/// This error occurs when deserialization of the params fails,
+	/// e.g `/profile/seun` for a `u64` id.
+	Err(DeError),
+	/// This error occurs when the request wasn't matched by a `Router`,
+	/// so there are no params.
+	None,
+}
+
+/// The error returned by `Request::body_as()`.
+///
+/// `From<BodyError>` is implemented for Response
+/// so you can use the `?` to unwrap or return an early response
+///
+/// ```rust, ignore
+/// #[service]
+/// fn UserService(req: Request, res: Response) {
+/// 	let NewUser { name } = req.body_as()?;
+/// 	// will return an error response with the
+/// 	// json '{ "error": "request body was empty" }' if BodyError::None,
+/// 	// a 415 if BodyError::Unsupported
+/// 	// or '{ "error": "{parse error}" }' if it failed to deserialize.
+/// }
+/// ```
+///
+#[derive(Debug)]
+pub enum BodyError {
+	/// This error occurs when deserialization of the body fails.
+	Err(DecodeError),
+	/// This error occurs when there's no decoder for the Content-Type of the
+	/// request, or it has none.
+	Unsupported(Option<String>),
+	/// This error occurs when the body wasn't read by the `BodyParser`.
+	None,
+}
+
+/// The error reported by the `Decoder` when the request body couldn't be
+/// deserialized.
+#[derive(Debug)]
+pub struct DecodeError(erased_serde::Error);
+
+impl fmt::Display for DecodeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(&self.0, f)
+	}
+}
+
+impl error::Error for DecodeError {
+	fn description(&self) -> &str {
+		self.0.description()
+	}
+}
+
+/// The error returned by `Request::form()` and `Request::form_as()`.
+///
+/// `form_as()` reads an url-encoded body buffered by the `BodyParser`, or the
+/// text fields of a multipart form parsed by the `MultiPart` middleware.
+///
+/// `From<FormError>` is implemented for Response
+/// so you can use the `?` to unwrap or return an early response
+///
+/// ```rust, ignore
+/// #[derive(Deserialize)]
+/// struct Post {
+/// 	title: String,
+/// 	tags: Vec<String>,
+/// }
+///
+/// #[service]
+/// fn PostService(req: Request, res: Response) {
+/// 	let Post { title, tags } = req.form_as()?;
+/// 	// will return an error response with the
+/// 	// json '{ "error": "form data was empty" }' if FormError::None
+/// 	// or '{ "error": "{parse error}" }' if it failed to deserialize.
+/// }
+/// ```
+///
+#[derive(Debug)]
+pub enum FormError {
+	/// This error occurs when deserialization of the text fields fails.
+	Err(DeError),
+	/// This error occurs when deserialization of an url-encoded body fails.
+	UrlEncoded(serde_qs::Error),
+	/// This error occurs when the form wasn't parsed by the `BodyParser` or
+	/// `MultiPart` middleware.
+	None,
+}
+
 impl Request {
 	pub(crate) fn new(parts: Parts, body: Body) -> Self {
 		Self {
@@ -141,12 +250,20 @@ impl Request {
 
 	/// Returns the IP of the connected client.
 	/// This should always be set, except in testing environments with
-	/// `FakeReactor`.
+	/// `FakeReactor` and for requests served over a Unix socket.
 	#[inline]
 	pub fn remote_ip(&self) -> Option<SocketAddr> {
 		self.remote
 	}
 
+	/// Returns the credentials of the process connected over a Unix socket.
+	/// This is `None` for requests served over tcp.
+	#[cfg(unix)]
+	#[inline]
+	pub fn peer_cred(&self) -> Option<&UCred> {
+		self.get::<UCred>()
+	}
+
 	/// Serializes the query string into a struct via serde.
 	///
 	///  # Examples
@@ -202,6 +319,37 @@ impl Request {
 		self.parts.extensions.get::<Params>()
 	}
 
+	/// Deserializes the url params for the request into a struct via serde.
+	///
+	/// The params are parsed into the type of each field, so integers, bools
+	/// and anything that deserializes from a string (e.g `Uuid`) just work.
+	///
+	/// ```rust, ignore
+	/// #[derive(Deserialize)]
+	/// struct Comment {
+	/// 	post_id: u64,
+	/// 	id: Uuid,
+	/// }
+	///
+	/// // mounted on "/posts/:post_id/comments/:id"
+	/// #[service]
+	/// pub fn CommentService(req: Request, res: Response) {
+	/// 	let Comment { post_id, id } = req.params_as()?;
+	/// }
+	/// ```
+	pub fn params_as<T>(&self) -> Result<T, ParamsError>
+	where
+		T: DeserializeOwned,
+	{
+		match self.params() {
+			Some(params) => {
+				de::from_pairs(params.iter().map(|(key, value)| (key, vec![value])))
+					.map_err(ParamsError::Err)
+			}
+			None => Err(ParamsError::None),
+		}
+	}
+
 	/// The request struct constains an `AnyMap` so that middlewares can append
 	/// additional information.
 	///
@@ -260,6 +408,9 @@ impl Request {
 
 	/// Serialize the request's json value into a struct.
 	///
+	/// Any Content-Type that mentions json is read as json, e.g
+	/// `application/json`, `text/json` or `application/vnd.api+json`.
+	///
 	/// Note that the json value needs to have been previously set on the
 	/// request by a middleware; otherwise this would return
 	/// `Err(JsonError::None)`.
@@ -267,18 +418,100 @@ impl Request {
 	where
 		T: DeserializeOwned,
 	{
-		match self.get::<Json>() {
-			Some(ref slice) => from_slice::<T>(slice).map_err(JsonError::Err),
+		match self.get::<Buffered>() {
+			Some(ref body) if body.mime.contains("json") => {
+				from_slice::<T>(body).map_err(JsonError::Err)
+			}
 			_ => Err(JsonError::None),
 		}
 	}
 
-	#[cfg(feature = "unstable")]
-	pub fn form(&self) -> Result<HashMap<String, String>, JsonError> {
-		match self.get::<MultiPartMap>() {
-			Some(ref map) => Ok(map.0.clone()),
-			_ => Err(JsonError::None),
+	/// Deserialize the request body into a struct, with the decoder registered
+	/// for its Content-Type in `Decoders`.
+	///
+	/// Note that the body needs to have been previously read by the
+	/// `BodyParser`; otherwise this would return `Err(BodyError::None)`, or
+	/// `Err(BodyError::Unsupported)` if there's no decoder for the
+	/// Content-Type.
+	///
+	/// ```rust, ignore
+	/// #[service]
+	/// pub fn CreateUser(req: Request, res: Response) {
+	/// 	// json, msgpack or cbor, depending on the Content-Type.
+	/// 	let user: NewUser = req.body_as()?;
+	/// }
+	/// ```
+	pub fn body_as<T>(&self) -> Result<T, BodyError>
+	where
+		T: DeserializeOwned,
+	{
+		let buffered = match self.get::<Buffered>() {
+			Some(buffered) => buffered,
+			None => {
+				let content_type = self
+					.headers()
+					.get(CONTENT_TYPE)
+					.and_then(|value| value.to_str().ok());
+				let supported = match (content_type, self.get::<Decoders>()) {
+					(Some(ct), Some(decoders)) => decoders.find(ct).is_some(),
+					(Some(ct), None) => Decoders::default().find(ct).is_some(),
+					(None, _) => false,
+				};
+				if supported {
+					return Err(BodyError::None);
+				}
+				return Err(BodyError::Unsupported(content_type.map(String::from)));
+			}
+		};
+
+		let mut value = None;
+		buffered
+			.decoder
+			.decode(&buffered.body, &mut |de| {
+				value = Some(erased_serde::deserialize::<T>(de)?);
+				Ok(())
+			})
+			.map_err(|err| BodyError::Err(DecodeError(err)))?;
+
+		value.ok_or(BodyError::None)
+	}
+
+	/// Get the multipart form, with every value of the text fields and the
+	/// uploaded files.
+	///
+	/// Note that the form needs to have been previously parsed by the
+	/// `MultiPart` middleware; otherwise this would return
+	/// `Err(FormError::None)`.
+	///
+	/// ```rust, ignore
+	/// #[service]
+	/// pub fn UploadService(req: Request, res: Response) {
+	/// 	let form = req.form()?;
+	/// 	for avatar in form.files("avatar") {
+	/// 		println!("{} saved at {:?}", avatar.filename, avatar.path);
+	/// 	}
+	/// }
+	/// ```
+	pub fn form(&self) -> Result<&Form, FormError> {
+		self.get::<Form>().ok_or(FormError::None)
+	}
+
+	/// Deserialize an `application/x-www-form-urlencoded` body, or the text
+	/// fields of a multipart form into a struct.
+	///
+	/// Note that the body needs to have been previously read by the
+	/// `BodyParser` or `MultiPart` middleware; otherwise this would return
+	/// `Err(FormError::None)`. Uploaded files are left out, get them with
+	/// `Request::form()`.
+	pub fn form_as<T>(&self) -> Result<T, FormError>
+	where
+		T: DeserializeOwned,
+	{
+		if let Some(body) = self.get::<UrlEncoded>() {
+			return serde_qs::from_bytes::<T>(body).map_err(FormError::UrlEncoded);
 		}
+
+		self.form()?.fields_as().map_err(FormError::Err)
 	}
 
 	/// Get a reference to the request body.
@@ -298,3 +531,51 @@ impl Request {
 		self.body = body
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use contrib::FakeReactor;
+	use core::Response;
+	use futures::{Future, Stream};
+	use hyper::StatusCode;
+	use proto::Json;
+	use routing::Router;
+	use std::collections::HashMap;
+
+	fn CommentService(
+		req: Request,
+		_res: Response,
+	) -> Result<Json<HashMap<String, u64>>, Response> {
+		Ok(Json(req.params_as()?))
+	}
+
+	fn reactor() -> FakeReactor {
+		FakeReactor::new(Router::new().get("/posts/:post_id/comments/:id", CommentService))
+	}
+
+	#[test]
+	fn it_deserializes_the_params_of_a_routed_request() {
+		let res = reactor()
+			.get("/posts/1/comments/2", None)
+			.expect("Should return ok");
+		let body = res.body().concat2().wait().unwrap();
+		let params: HashMap<String, u64> = from_slice(&body).unwrap();
+
+		assert_eq!(params["post_id"], 1);
+		assert_eq!(params["id"], 2);
+	}
+
+	#[test]
+	fn it_responds_with_400_for_invalid_params() {
+		let res = reactor()
+			.get("/posts/seun/comments/2", None)
+			.expect_err("Should return an error");
+		assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+		assert_eq!(res.headers()[CONTENT_TYPE], "application/json");
+
+		let body = res.body().concat2().wait().unwrap();
+		let json: serde_json::Value = from_slice(&body).unwrap();
+		assert!(json["error"].is_string());
+	}
+}
@@ -1,16 +1,59 @@
-use core::file;
-use futures::prelude::*;
-use http::response::Parts;
+use contrib::Encoders;
+use core::{
+	file,
+	range::{self, ByteRange, Ranges},
+	validators,
+	Request,
+};
+use futures::{
+	future::{self, Either},
+	prelude::*,
+	stream,
+};
+use http::{response::Parts, status::InvalidStatusCode};
 use hyper::{
 	self,
-	header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, LOCATION},
+	header::{
+		HeaderMap,
+		HeaderName,
+		HeaderValue,
+		ACCEPT,
+		ACCEPT_ENCODING,
+		ACCEPT_RANGES,
+		CONTENT_LENGTH,
+		CONTENT_RANGE,
+		CONTENT_TYPE,
+		ETAG,
+		IF_MODIFIED_SINCE,
+		IF_NONE_MATCH,
+		IF_RANGE,
+		LAST_MODIFIED,
+		LOCATION,
+		ORIGIN,
+		RANGE,
+		VARY,
+	},
 	Body,
+	Chunk,
+	Method,
 	StatusCode,
+	Uri,
 	Version,
 };
 use mime_guess::guess_mime_type;
-use std::{fmt::Debug, path::Path};
-use tokio::{fs::File, io::ErrorKind};
+use serde::ser::Serialize;
+use serde_json;
+use std::{
+	fmt::Debug,
+	io,
+	path::Path,
+	sync::Arc,
+	time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+	fs::{self, File},
+	io::ErrorKind,
+};
 
 #[derive(Debug)]
 pub struct Response {
@@ -18,11 +61,155 @@ pub struct Response {
 	pub(crate) body: Body,
 }
 
+/// The method, uri and headers of the request a `Response` is being built
+/// for.
+///
+/// The `Response` passed to services carries this, so that a
+/// `MiddleWare<Response>` can inspect the request, e.g the `Origin` header.
+/// Only the `Origin`, `Accept-Encoding`, `Range`, `If-Range`, `If-None-Match`
+/// and `If-Modified-Since` headers are kept, they're what `Cors`,
+/// `Compression` and `Response::with_file` read.
+#[derive(Clone, Debug)]
+pub struct RequestHead {
+	pub method: Method,
+	pub uri: Uri,
+	pub headers: HeaderMap<HeaderValue>,
+}
+
+/// The request headers kept in a `RequestHead`.
+const HEAD_HEADERS: [HeaderName; 6] = [
+	ORIGIN,
+	ACCEPT_ENCODING,
+	RANGE,
+	IF_RANGE,
+	IF_NONE_MATCH,
+	IF_MODIFIED_SINCE,
+];
+
+/// A status code, either a `StatusCode` or its `u16` value.
+pub trait IntoStatusCode {
+	fn into_status_code(self) -> Result<StatusCode, InvalidStatusCode>;
+}
+
+impl IntoStatusCode for u16 {
+	fn into_status_code(self) -> Result<StatusCode, InvalidStatusCode> {
+		StatusCode::from_u16(self)
+	}
+}
+
+impl IntoStatusCode for StatusCode {
+	fn into_status_code(self) -> Result<StatusCode, InvalidStatusCode> {
+		Ok(self)
+	}
+}
+
+impl<'a> From<&'a Request> for RequestHead {
+	fn from(req: &'a Request) -> RequestHead {
+		let mut headers = HeaderMap::new();
+		for name in HEAD_HEADERS.iter() {
+			for value in req.headers().get_all(name) {
+				headers.append(name.clone(), value.clone());
+			}
+		}
+
+		RequestHead {
+			method: req.method().clone(),
+			uri: req.uri().clone(),
+			headers,
+		}
+	}
+}
+
 impl Response {
 	pub fn new() -> Self {
 		Response::default()
 	}
 
+	/// Creates a response that carries the `RequestHead` of the request.
+	///
+	/// Prefer this over `Response::new()` when you don't reuse the response
+	/// passed to your service, so response middlewares can still see the
+	/// request.
+	pub fn for_request(req: &Request) -> Self {
+		let mut res = Response::default();
+		res.set(Arc::new(RequestHead::from(req)));
+		res
+	}
+
+	/// Creates a response for the request with `value` as the body, serialized
+	/// in the format the client prefers according to its `Accept` header.
+	///
+	/// The formats come from the `Encoders` mounted on the request, or the
+	/// default ones. When none of them is acceptable the response is a
+	/// `406 Not Acceptable`.
+	///
+	/// ```rust, ignore
+	/// #[service]
+	/// pub fn UserService(req: Request, res: Response) {
+	/// 	let user = User { name: "seun".into() };
+	/// 	// json, msgpack or cbor, depending on the Accept header.
+	/// 	return Ok(Response::negotiate(&req, &user));
+	/// }
+	/// ```
+	pub fn negotiate<T: Serialize>(req: &Request, value: &T) -> Self {
+		let mut res = Response::for_request(req);
+		res.headers_mut()
+			.append(VARY, HeaderValue::from_static("Accept"));
+
+		let accept = req
+			.headers()
+			.get(ACCEPT)
+			.and_then(|value| value.to_str().ok());
+		let negotiated = match req.get::<Encoders>() {
+			Some(encoders) => encoders.negotiate(accept),
+			None => Encoders::default().negotiate(accept),
+		};
+		let (mime, encoder) = match negotiated {
+			Some(negotiated) => negotiated,
+			None => return res.notAcceptable(),
+		};
+
+		match encoder.encode(value) {
+			Ok(body) => {
+				let content_type = if mime.starts_with("text/") {
+					format!("{}; charset=utf-8", mime)
+				} else {
+					mime
+				};
+				if let Ok(content_type) = HeaderValue::from_str(&content_type) {
+					res.headers_mut().insert(CONTENT_TYPE, content_type);
+				}
+				res.with_body(body)
+			}
+			Err(err) => {
+				error!("Error encoding the response as {}: {}", mime, err);
+				res.internalServerError()
+			}
+		}
+	}
+
+	/// Returns the `RequestHead` of the request this response is for.
+	#[inline]
+	pub fn request(&self) -> Option<&RequestHead> {
+		self.get::<Arc<RequestHead>>().map(|head| &**head)
+	}
+
+	/// Returns a shared handle to the `RequestHead`, it's cheap to clone.
+	pub(crate) fn request_head(&self) -> Option<Arc<RequestHead>> {
+		self.get::<Arc<RequestHead>>().cloned()
+	}
+
+	/// Sets the `RequestHead` on a response that doesn't carry one yet, e.g one
+	/// built with `Response::new()`.
+	pub(crate) fn carry_request(mut self, head: Option<&Arc<RequestHead>>) -> Self {
+		if let Some(head) = head {
+			if self.request().is_none() {
+				self.set(head.clone());
+			}
+		}
+		self
+	}
+
 	/// Get the HTTP version of this response.
 	#[inline]
 	pub fn version(&self) -> Version {
@@ -64,20 +251,55 @@ impl Response {
 	/// }
 	/// ```
 	///
+	/// An invalid status code is logged and the status is set to 500, use
+	/// `try_with_status` to handle it yourself.
 	#[inline]
-	pub fn set_status(&mut self, status: u16) {
-		self.parts.status = StatusCode::from_u16(status).unwrap();
+	pub fn set_status<S: IntoStatusCode>(&mut self, status: S) {
+		self.parts.status = match status.into_status_code() {
+			Ok(status) => status,
+			Err(err) => {
+				error!("Error setting the response status: {}", err);
+				StatusCode::INTERNAL_SERVER_ERROR
+			}
+		};
 	}
 
 	/// Set the status and move the Response.
 	///
 	/// Useful for the "builder-style" pattern.
 	#[inline]
-	pub fn with_status(mut self, status: u16) -> Self {
-		self.parts.status = StatusCode::from_u16(status).unwrap();
+	pub fn with_status<S: IntoStatusCode>(mut self, status: S) -> Self {
+		self.set_status(status);
 		self
 	}
 
+	/// Set the status and move the Response, failing if the status code is
+	/// invalid.
+	#[inline]
+	pub fn try_with_status<S: IntoStatusCode>(mut self, status: S) -> Result<Self, InvalidStatusCode> {
+		self.parts.status = status.into_status_code()?;
+		Ok(self)
+	}
+
+	/// Serialize `value` as the json body of the response, failing if it
+	/// can't be serialized.
+	///
+	/// ```rust, ignore
+	/// pub fn UserService(req: Request, res: Response) {
+	/// 	let user = User::find(1);
+	/// 	match res.try_json(&user) {
+	/// 		Ok(res) => Ok(res),
+	/// 		Err(err) => Err((500, err.to_string()).into()),
+	/// 	}
+	/// }
+	/// ```
+	pub fn try_json<T: Serialize>(mut self, value: &T) -> Result<Self, serde_json::Error> {
+		let body = serde_json::to_vec(value)?;
+		self.headers_mut()
+			.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+		Ok(self.with_body(body))
+	}
+
 	/// Set a text/plain response.
 	#[inline]
 	pub fn text<T: Into<String>>(&mut self, body: T) {
@@ -117,7 +339,20 @@ impl Response {
 
 	/// Respond with a file.
 	/// this method will set the appropriate Content-type and Content-Length
-	/// headers unfortunately, this doesn't support byte ranges, yet.
+	/// headers.
+	///
+	/// Byte ranges are supported when the response carries a `RequestHead`, i.e
+	/// it was passed to your service or created with `Response::for_request`.
+	/// A `Range` header gets a 206 Partial Content, multiple ranges are sent as
+	/// `multipart/byteranges`, and ranges outside the file get a 416.
+	///
+	/// A weak `ETag` and `Last-Modified` header are always set, and a request
+	/// whose `If-None-Match` or `If-Modified-Since` matches gets a 304 Not
+	/// Modified with no body, without the file being opened.
+	///
+	/// The 206, 304 and 416 responses are all `Ok`, the future only fails
+	/// with a 404 or a 500 if the file can't be read.
+	///
 	/// The file is streamed asynchronously from the filesystem to the client
 	/// with the Content-Encoding: chunked.
 	pub fn with_file<P>(mut self, pathbuf: P) -> impl Future<Item = Response, Error = Response>
@@ -125,36 +360,121 @@ impl Response {
 		P: AsRef<Path> + Send + Clone + Debug + 'static,
 	{
 		let path_clone = pathbuf.clone();
-
-		File::open(pathbuf)
-			.and_then(file::metadata)
-			.then(move |result| {
-				match result {
-					Ok((file, meta)) => {
-						let mime_type = guess_mime_type(path_clone.clone());
+		// conditional and range requests only apply to GET and HEAD.
+		let head = match self.request_head() {
+			Some(head) if head.method == Method::GET || head.method == Method::HEAD => Some(head),
+			_ => None,
+		};
+
+		// the validators only need the metadata, so a fresh copy is answered
+		// without opening the file.
+		fs::metadata(pathbuf.clone()).then(move |result| {
+			let meta = match result {
+				Ok(meta) => meta,
+				Err(err) => return Either::A(future::err(fileError(self, err))),
+			};
+			let empty = HeaderMap::new();
+			let headers = match head {
+				Some(ref head) => &head.headers,
+				None => &empty,
+			};
+			let mime_type = guess_mime_type(path_clone);
+			let size = meta.len();
+			let modified = meta.modified().ok();
+			let etag = validators::etag(&meta);
+
+			self.headers_mut()
+				.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+			self.headers_mut()
+				.insert(ETAG, HeaderValue::from_str(&etag).unwrap());
+			if let Some(modified) = modified {
+				self.headers_mut().insert(
+					LAST_MODIFIED,
+					HeaderValue::from_str(&validators::last_modified(modified)).unwrap(),
+				);
+			}
+
+			if validators::not_modified(headers, &etag, modified) {
+				self.set_status(304);
+				return Either::A(future::ok(self));
+			}
+
+			let header = |name: HeaderName| headers.get(name).and_then(|value| value.to_str().ok());
+			let ranges = match header(RANGE) {
+				Some(range) if range::if_range_matches(header(IF_RANGE), modified) => {
+					range::parse(range, size)
+				}
+				_ => Ranges::Full,
+			};
+
+			if ranges == Ranges::Unsatisfiable {
+				self.set_status(416);
+				self.headers_mut().insert(
+					CONTENT_RANGE,
+					HeaderValue::from_str(&format!("bytes */{}", size)).unwrap(),
+				);
+				return Either::A(future::ok(self));
+			}
+
+			Either::B(File::open(pathbuf).then(move |result| {
+				let file = match result {
+					Ok(file) => file,
+					Err(err) => return Err(fileError(self, err)),
+				};
+
+				match ranges {
+					Ranges::Satisfiable(ref ranges) if ranges.len() == 1 => {
+						let range = ranges[0];
+						self.set_status(206);
+						self.headers_mut().insert(
+							CONTENT_RANGE,
+							HeaderValue::from_str(&range.content_range(size)).unwrap(),
+						);
 						self.headers_mut().insert(
 							CONTENT_LENGTH,
-							HeaderValue::from_str(&meta.len().to_string()).unwrap(),
+							HeaderValue::from_str(&range.len().to_string()).unwrap(),
 						);
 						self.headers_mut().insert(
 							CONTENT_TYPE,
 							HeaderValue::from_str(mime_type.as_ref()).unwrap(),
 						);
-
-						self.body = Body::wrap_stream(file::stream(file));
-
-						Ok(self)
+						self.body =
+							Body::wrap_stream(file::stream_range(file, range.start, range.len()));
 					}
-					Err(err) => {
-						error!("Error responding with a file: {}", err);
-						match err.kind() {
-							ErrorKind::NotFound => self.set_status(404),
-							_ => self.set_status(500),
-						};
-						Err(self)
+					Ranges::Satisfiable(ref ranges) => {
+						let (boundary, length, body) =
+							byteranges(file, ranges, size, mime_type.as_ref());
+						self.set_status(206);
+						self.headers_mut().insert(
+							CONTENT_LENGTH,
+							HeaderValue::from_str(&length.to_string()).unwrap(),
+						);
+						self.headers_mut().insert(
+							CONTENT_TYPE,
+							HeaderValue::from_str(&format!(
+								"multipart/byteranges; boundary={}",
+								boundary
+							))
+							.unwrap(),
+						);
+						self.body = body;
 					}
-				}
-			})
+					_ => {
+						self.headers_mut().insert(
+							CONTENT_LENGTH,
+							HeaderValue::from_str(&size.to_string()).unwrap(),
+						);
+						self.headers_mut().insert(
+							CONTENT_TYPE,
+							HeaderValue::from_str(mime_type.as_ref()).unwrap(),
+						);
+						self.body = Body::wrap_stream(file::stream(file));
+					}
+				};
+
+				Ok(self)
+			}))
+		})
 	}
 
 	/// Set the body and move the Response.
@@ -238,6 +558,50 @@ impl Response {
 	}
 }
 
+/// Sets the status for a file that couldn't be read, 404 if it doesn't exist.
+fn fileError(mut res: Response, err: io::Error) -> Response {
+	error!("Error responding with a file: {}", err);
+	match err.kind() {
+		ErrorKind::NotFound => res.set_status(404),
+		_ => res.set_status(500),
+	};
+	res
+}
+
+/// Builds a `multipart/byteranges` body for the ranges of the file, returns
+/// the boundary, the length of the body and the body.
+fn byteranges(
+	file: File,
+	ranges: &[ByteRange],
+	size: u64,
+	content_type: &str,
+) -> (String, u64, Body) {
+	let boundary = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|time| format!("{:x}{:x}", time.as_secs(), time.subsec_nanos()))
+		.unwrap_or_default();
+	let boundary = format!("arc-reactor-{}", boundary);
+
+	let mut length = 0;
+	let mut parts = Vec::with_capacity(ranges.len());
+	for range in ranges {
+		let head = format!(
+			"\r\n--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+			boundary,
+			content_type,
+			range.content_range(size)
+		);
+		length += head.len() as u64 + range.len();
+		parts.push((Chunk::from(head), range.start, range.len()));
+	}
+
+	let tail = format!("\r\n--{}--\r\n", boundary);
+	length += tail.len() as u64;
+	let body = file::stream_ranges(file, parts).chain(stream::once(Ok(Chunk::from(tail))));
+
+	(boundary, length, Body::wrap_stream(body))
+}
+
 impl Default for Response {
 	fn default() -> Response {
 		let (parts, body) = hyper::Response::new(Body::empty()).into_parts();
@@ -248,3 +612,120 @@ impl Default for Response {
 pub fn res() -> Response {
 	Response::default()
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use http::request::Builder;
+	use std::{env, fs, path::PathBuf};
+	use tokio::runtime::Runtime;
+
+	/// Creates a file with the digits 0 to 9 in it.
+	fn digits(name: &str) -> PathBuf {
+		let path =
+			env::temp_dir().join(format!("arc-reactor-{}-{}.txt", name, ::std::process::id()));
+		fs::write(&path, "0123456789").unwrap();
+		path
+	}
+
+	/// Responds with the file and reads the whole body.
+	fn respond(path: PathBuf, headers: &[(&str, &str)]) -> (StatusCode, HeaderMap, Vec<u8>) {
+		let mut builder = Builder::new();
+		builder.uri("/digits.txt");
+		for &(name, value) in headers {
+			builder.header(name, value);
+		}
+		let req: Request = builder.body(Body::empty()).unwrap().into();
+
+		let mut runtime = Runtime::new().unwrap();
+		let res = match runtime.block_on(Response::for_request(&req).with_file(path)) {
+			Ok(res) => res,
+			Err(res) => panic!("with_file failed with a {}", res.status()),
+		};
+		let (status, headers) = (res.status(), res.headers().clone());
+		let body = runtime.block_on(res.body().concat2()).unwrap();
+
+		(status, headers, body.to_vec())
+	}
+
+	#[test]
+	fn it_responds_with_a_single_range() {
+		let path = digits("single-range");
+
+		let (status, headers, body) = respond(path.clone(), &[("range", "bytes=2-5")]);
+		assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+		assert_eq!(headers[CONTENT_RANGE], "bytes 2-5/10");
+		assert_eq!(headers[CONTENT_LENGTH], "4");
+		assert_eq!(body, b"2345");
+
+		// overlapping ranges are sent once.
+		let (status, headers, body) = respond(path.clone(), &[("range", "bytes=0-,0-,0-")]);
+		assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+		assert_eq!(headers[CONTENT_RANGE], "bytes 0-9/10");
+		assert_eq!(body, b"0123456789");
+
+		fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn it_responds_with_multiple_ranges() {
+		let path = digits("multiple-ranges");
+
+		let (status, headers, body) = respond(path.clone(), &[("range", "bytes=5-6, 0-1")]);
+		assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+		let content_type = headers[CONTENT_TYPE].to_str().unwrap();
+		assert!(content_type.starts_with("multipart/byteranges; boundary="));
+		assert_eq!(headers[CONTENT_LENGTH], body.len().to_string().as_str());
+
+		let body = String::from_utf8(body).unwrap();
+		let first = body
+			.find("Content-Range: bytes 0-1/10\r\n\r\n01\r\n")
+			.unwrap();
+		let second = body
+			.find("Content-Range: bytes 5-6/10\r\n\r\n56\r\n")
+			.unwrap();
+		assert!(first < second);
+		let boundary = &content_type["multipart/byteranges; boundary=".len()..];
+		assert!(body.ends_with(&format!("\r\n--{}--\r\n", boundary)));
+
+		fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn it_responds_with_304_for_fresh_copies() {
+		let path = digits("not-modified");
+
+		let (status, headers, body) = respond(path.clone(), &[]);
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(body, b"0123456789");
+		let etag = headers[ETAG].to_str().unwrap();
+		let modified = headers[LAST_MODIFIED].to_str().unwrap();
+
+		let (status, headers, body) = respond(path.clone(), &[("if-none-match", etag)]);
+		assert_eq!(status, StatusCode::NOT_MODIFIED);
+		assert_eq!(headers[ETAG], etag);
+		assert!(body.is_empty());
+
+		let (status, _, body) = respond(path.clone(), &[("if-modified-since", modified)]);
+		assert_eq!(status, StatusCode::NOT_MODIFIED);
+		assert!(body.is_empty());
+
+		let (status, _, body) = respond(path.clone(), &[("if-none-match", "W/\"stale\"")]);
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(body, b"0123456789");
+
+		fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn it_responds_with_416_for_unsatisfiable_ranges() {
+		let path = digits("unsatisfiable-range");
+
+		let (status, headers, body) = respond(path.clone(), &[("range", "bytes=20-")]);
+		assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+		assert_eq!(headers[CONTENT_RANGE], "bytes */10");
+		assert!(body.is_empty());
+
+		fs::remove_file(path).unwrap();
+	}
+}
@@ -9,13 +9,28 @@ use hyper::{
 };
 use proto::{ArcHandler, ArcService};
 use std::{mem, net::SocketAddr, panic::AssertUnwindSafe};
+#[cfg(unix)]
+use tokio_uds::UCred;
 // The only reason this exists is so I can pass the
 // clientIp to the ArcService.
 pub(crate) struct RootService {
 	pub(crate) remote_ip: Option<SocketAddr>,
+	#[cfg(unix)]
+	pub(crate) peer_cred: Option<UCred>,
 	pub(crate) service: ArcHandler,
 }
 
+impl RootService {
+	pub(crate) fn new(service: ArcHandler, remote_ip: Option<SocketAddr>) -> Self {
+		RootService {
+			remote_ip,
+			#[cfg(unix)]
+			peer_cred: None,
+			service,
+		}
+	}
+}
+
 impl Service for RootService {
 	type ReqBody = Body;
 	type ResBody = Body;
@@ -25,7 +40,13 @@ impl Service for RootService {
 	fn call(&mut self, req: hyper::Request<Self::ReqBody>) -> Self::Future {
 		let mut request: Request = req.into();
 		request.remote = mem::replace(&mut self.remote_ip, None);
-		let res = Response::new();
+		#[cfg(unix)]
+		{
+			if let Some(ref cred) = self.peer_cred {
+				request.set(cred.clone());
+			}
+		}
+		let res = Response::for_request(&request);
 		let responseFuture = AssertUnwindSafe(self.service.call(request, res)).catch_unwind();
 
 		let responseFuture = responseFuture.then(|result| {
@@ -0,0 +1,106 @@
+//! Validators for files, used to answer conditional requests.
+use hyper::header::{HeaderMap, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH};
+use hyperx::header::HttpDate;
+use std::{
+	fs::Metadata,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+fn seconds(time: SystemTime) -> Option<u64> {
+	time.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+}
+
+/// A weak entity tag derived from the size and modification time of the
+/// file, it's cheap to compute but can't tell apart two edits made within
+/// the same second that don't change the size.
+pub(crate) fn etag(meta: &Metadata) -> String {
+	let modified = meta.modified().ok().and_then(seconds).unwrap_or(0);
+	format!("W/\"{:x}-{:x}\"", meta.len(), modified)
+}
+
+/// The value of the `Last-Modified` header for this modification time.
+pub(crate) fn last_modified(modified: SystemTime) -> String {
+	HttpDate::from(modified).to_string()
+}
+
+/// Returns true if the client's cached copy is still fresh, according to the
+/// `If-None-Match` header, or `If-Modified-Since` when there's no
+/// `If-None-Match`.
+pub(crate) fn not_modified(
+	headers: &HeaderMap<HeaderValue>,
+	etag: &str,
+	modified: Option<SystemTime>,
+) -> bool {
+	if headers.contains_key(IF_NONE_MATCH) {
+		// If-None-Match uses the weak comparison, so the W/ prefix is ignored.
+		let opaque = |tag: &str| {
+			let tag = tag.trim();
+			if tag.starts_with("W/") {
+				tag[2..].to_owned()
+			} else {
+				tag.to_owned()
+			}
+		};
+		let etag = opaque(etag);
+
+		return headers
+			.get_all(IF_NONE_MATCH)
+			.iter()
+			.filter_map(|value| value.to_str().ok())
+			.flat_map(|value| value.split(','))
+			.any(|tag| tag.trim() == "*" || opaque(tag) == etag);
+	}
+
+	let since = headers
+		.get(IF_MODIFIED_SINCE)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.parse::<HttpDate>().ok())
+		.and_then(|date| seconds(SystemTime::from(date)));
+
+	match (since, modified.and_then(seconds)) {
+		(Some(since), Some(modified)) => modified <= since,
+		_ => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+
+	#[test]
+	fn it_matches_if_none_match() {
+		let mut headers = HeaderMap::new();
+		headers.insert(
+			IF_NONE_MATCH,
+			HeaderValue::from_static("\"abc\", W/\"10-5\""),
+		);
+
+		assert!(not_modified(&headers, "W/\"10-5\"", None));
+		assert!(!not_modified(&headers, "W/\"10-6\"", None));
+
+		headers.insert(IF_NONE_MATCH, HeaderValue::from_static("*"));
+		assert!(not_modified(&headers, "W/\"10-6\"", None));
+	}
+
+	#[test]
+	fn it_matches_if_modified_since() {
+		let modified = UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+		let mut headers = HeaderMap::new();
+		headers.insert(
+			IF_MODIFIED_SINCE,
+			HeaderValue::from_str(&last_modified(modified)).unwrap(),
+		);
+
+		assert!(not_modified(&headers, "W/\"10-5\"", Some(modified)));
+		assert!(!not_modified(
+			&headers,
+			"W/\"10-5\"",
+			Some(modified + Duration::from_secs(60))
+		));
+
+		// If-None-Match takes precedence.
+		headers.insert(IF_NONE_MATCH, HeaderValue::from_static("\"abc\""));
+		assert!(!not_modified(&headers, "W/\"10-5\"", Some(modified)));
+	}
+}
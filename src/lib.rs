@@ -105,17 +105,29 @@ extern crate hyper;
 extern crate impl_service;
 extern crate native_tls;
 extern crate percent_encoding;
+#[macro_use]
 extern crate serde;
 extern crate serde_qs;
 extern crate tokio;
+extern crate tokio_threadpool;
 extern crate tokio_tls;
+#[cfg(unix)]
+extern crate tokio_uds;
 #[macro_use]
 extern crate serde_json;
+extern crate brotli;
 extern crate bytes;
+extern crate erased_serde;
+extern crate flate2;
 extern crate hyperx;
 extern crate mime;
 extern crate mime_guess;
 extern crate regex;
+#[cfg(feature = "msgpack")]
+extern crate rmp_serde;
+#[cfg(feature = "cbor")]
+extern crate serde_cbor;
+extern crate sha2;
 
 #[macro_use]
 pub mod proto;
@@ -133,5 +145,13 @@ pub mod prelude {
 	pub use futures::prelude::{async_block, await};
 	#[cfg(feature = "unstable")]
 	pub use impl_service::{middleware, service};
-	pub use proto::{ArcHandler, ArcService, FutureResponse, MiddleWare, MiddleWareFuture};
+	pub use proto::{
+		ArcHandler,
+		ArcService,
+		FutureResponse,
+		IntoResponse,
+		Json,
+		MiddleWare,
+		MiddleWareFuture,
+	};
 }
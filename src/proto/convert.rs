@@ -1,37 +1,47 @@
-use core::{JsonError, QueryParseError, Request, Response};
-use hyper::{
-	self,
-	header::{HeaderValue, CONTENT_TYPE},
-	Body,
+use core::{
+	BodyError,
+	FormError,
+	IntoStatusCode,
+	JsonError,
+	ParamsError,
+	QueryParseError,
+	Request,
+	Response,
 };
-use mime;
+use hyper::{self, Body, StatusCode};
 use serde::ser::Serialize;
-use serde_json::to_vec;
+
+/// Serializes `value` as the json body of a response with `status`, a value
+/// that can't be serialized is logged and turned into a 500.
+fn json_response<S: IntoStatusCode, T: Serialize>(status: S, value: &T) -> Response {
+	match Response::new().try_json(value) {
+		Ok(res) => res.with_status(status),
+		Err(err) => {
+			error!("Error serializing json response: {}", err);
+			Response::new().internalServerError()
+		}
+	}
+}
 
 impl<T: Serialize> From<(u16, T)> for Response {
 	fn from(tuple: (u16, T)) -> Response {
-		let body = to_vec(&tuple.1).unwrap();
-
-		let mut res = Response::new();
-		res.headers_mut().insert(
-			CONTENT_TYPE,
-			HeaderValue::from_str(mime::APPLICATION_JSON.as_ref()).unwrap(),
-		);
+		json_response(tuple.0, &tuple.1)
+	}
+}
 
-		res.with_status(tuple.0).with_body(body)
+// with specialization the blanket `From<T>` below would overlap this impl,
+// as `http` is free to implement `Serialize` for `StatusCode` in the future.
+#[cfg(not(feature = "unstable"))]
+impl<T: Serialize> From<(StatusCode, T)> for Response {
+	fn from(tuple: (StatusCode, T)) -> Response {
+		json_response(tuple.0, &tuple.1)
 	}
 }
 
 #[cfg(feature = "unstable")]
 impl<T: Serialize> From<T> for Response {
 	default fn from(json: T) -> Response {
-		let body = to_vec(&json).unwrap();
-		let mut res = Response::new();
-		res.headers_mut().insert(
-			CONTENT_TYPE,
-			HeaderValue::from_str(mime::APPLICATION_JSON.as_ref()).unwrap(),
-		);
-		res.with_body(body)
+		json_response(200, &json)
 	}
 }
 
@@ -60,26 +70,14 @@ impl From<JsonError> for Response {
 				let json = json!({
 					"error": "Json was empty",
 				});
-				let body = to_vec(&json).unwrap();
-				let mut res = Response::new();
-				res.headers_mut().insert(
-					CONTENT_TYPE,
-					HeaderValue::from_str(mime::APPLICATION_JSON.as_ref()).unwrap(),
-				);
-				res.badRequest().with_body(body)
+				json_response(400, &json)
 			}
 			JsonError::Err(e) => {
 				error!("serde deserialization error: {}", e);
 				let json = json!({
 					"error": format!("{}", e),
 				});
-				let body = to_vec(&json).unwrap();
-				let mut res = Response::new();
-				res.headers_mut().insert(
-					CONTENT_TYPE,
-					HeaderValue::from_str(mime::APPLICATION_JSON.as_ref()).unwrap(),
-				);
-				res.badRequest().with_body(body)
+				json_response(400, &json)
 			}
 		}
 	}
@@ -93,13 +91,7 @@ impl From<QueryParseError> for Response {
 				let json = json!({
 					"error": "query data was empty",
 				});
-				let body = to_vec(&json).unwrap();
-				let mut res = Response::new();
-				res.headers_mut().insert(
-					CONTENT_TYPE,
-					HeaderValue::from_str(mime::APPLICATION_JSON.as_ref()).unwrap(),
-				);
-				res.badRequest().with_body(body)
+				json_response(400, &json)
 			}
 
 			QueryParseError::Err(err) => {
@@ -107,14 +99,128 @@ impl From<QueryParseError> for Response {
 				let json = json!({
 					"error": format!("{}", err),
 				});
-				let body = to_vec(&json).unwrap();
-				let mut res = Response::new();
-				res.headers_mut().insert(
-					CONTENT_TYPE,
-					HeaderValue::from_str(mime::APPLICATION_JSON.as_ref()).unwrap(),
-				);
-				res.badRequest().with_body(body)
+				json_response(400, &json)
+			}
+		}
+	}
+}
+
+impl From<ParamsError> for Response {
+	fn from(error: ParamsError) -> Response {
+		match error {
+			ParamsError::None => {
+				error!("No route params");
+				let json = json!({
+					"error": "route params were empty",
+				});
+				json_response(400, &json)
+			}
+
+			ParamsError::Err(err) => {
+				error!("Error deserializing route params: {}", err);
+				let json = json!({
+					"error": format!("{}", err),
+				});
+				json_response(400, &json)
+			}
+		}
+	}
+}
+
+impl From<FormError> for Response {
+	fn from(error: FormError) -> Response {
+		match error {
+			FormError::None => {
+				error!("No multipart form");
+				let json = json!({
+					"error": "form data was empty",
+				});
+				json_response(400, &json)
+			}
+
+			FormError::UrlEncoded(err) => {
+				error!("Error deserializing url-encoded form: {}", err);
+				let json = json!({
+					"error": format!("{}", err),
+				});
+				json_response(400, &json)
+			}
+
+			FormError::Err(err) => {
+				error!("Error deserializing form fields: {}", err);
+				let json = json!({
+					"error": format!("{}", err),
+				});
+				json_response(400, &json)
 			}
 		}
 	}
 }
+
+impl From<BodyError> for Response {
+	fn from(error: BodyError) -> Response {
+		match error {
+			BodyError::None => {
+				error!("No request body");
+				let json = json!({
+					"error": "request body was empty",
+				});
+				json_response(400, &json)
+			}
+
+			BodyError::Unsupported(content_type) => {
+				let content_type = content_type.unwrap_or_else(|| "none".into());
+				error!("No decoder for Content-Type: {}", content_type);
+				let json = json!({
+					"error": format!("Unsupported Content-Type: {}", content_type),
+				});
+				json_response(415, &json)
+			}
+
+			BodyError::Err(err) => {
+				error!("Error deserializing request body: {}", err);
+				let json = json!({
+					"error": format!("{}", err),
+				});
+				json_response(400, &json)
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use hyper::header::CONTENT_TYPE;
+	use std::collections::HashMap;
+
+	#[test]
+	fn it_responds_with_500_instead_of_panicking() {
+		let res: Response = (400, "bad request").into();
+		assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+		assert_eq!(res.headers()[CONTENT_TYPE], "application/json");
+
+		// json object keys must be strings.
+		let mut map = HashMap::new();
+		map.insert((1, 2), 3);
+		let res: Response = (200, map).into();
+		assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+		let res: Response = (1000, "invalid status").into();
+		assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+		assert!(Response::new().try_with_status(1000).is_err());
+		assert_eq!(
+			Response::new().with_status(StatusCode::CREATED).status(),
+			StatusCode::CREATED
+		);
+	}
+
+	#[cfg(not(feature = "unstable"))]
+	#[test]
+	fn it_converts_status_code_tuples() {
+		let json = json!({ "error": "not found" });
+		let res: Response = (StatusCode::NOT_FOUND, json).into();
+		assert_eq!(res.status(), StatusCode::NOT_FOUND);
+		assert_eq!(res.headers()[CONTENT_TYPE], "application/json");
+	}
+}
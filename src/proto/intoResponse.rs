@@ -0,0 +1,202 @@
+use bytes::Bytes;
+use core::Response;
+use futures::{future, Future};
+use hyper::{
+	header::{HeaderMap, HeaderValue, CONTENT_TYPE},
+	StatusCode,
+};
+use proto::FutureResponse;
+use serde::ser::Serialize;
+
+/// Conversion into a `FutureResponse`, anything that implements it can be
+/// returned from a service.
+///
+/// An `Err` is returned as an error response, so response middlewares
+/// still get to see it.
+///
+/// ```rust, ignore
+/// fn ProfileService(req: Request, _res: Response) -> Result<Json<Profile>, (StatusCode, String)> {
+/// 	let Profile { id } = req.params_as().map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
+/// 	Ok(Json(Profile::find(id)))
+/// }
+///
+/// fn HealthService(_req: Request, _res: Response) -> &'static str {
+/// 	"OK"
+/// }
+/// ```
+pub trait IntoResponse {
+	fn into_response(self) -> FutureResponse;
+}
+
+/// Serializes `T` as the json body of the response.
+///
+/// ```rust, ignore
+/// fn UserService(_req: Request, _res: Response) -> Json<User> {
+/// 	Json(User { name: "seun".into() })
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Json<T>(pub T);
+
+/// Applies `f` to the response, whether it's a success or an error.
+fn map<F>(future: FutureResponse, f: F) -> FutureResponse
+where
+	F: FnOnce(Response) -> Response + Send + 'static,
+{
+	Box::new(future.then(move |result| {
+		match result {
+			Ok(res) => Ok(f(res)),
+			Err(res) => Err(f(res)),
+		}
+	}))
+}
+
+fn with_content_type(body: Response, content_type: &'static str) -> FutureResponse {
+	let mut res = body;
+	res.headers_mut()
+		.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+	Box::new(future::ok(res))
+}
+
+impl IntoResponse for Response {
+	fn into_response(self) -> FutureResponse {
+		Box::new(future::ok(self))
+	}
+}
+
+impl IntoResponse for FutureResponse {
+	fn into_response(self) -> FutureResponse {
+		self
+	}
+}
+
+impl IntoResponse for String {
+	fn into_response(self) -> FutureResponse {
+		with_content_type(Response::new().with_body(self), "text/plain; charset=utf-8")
+	}
+}
+
+impl IntoResponse for &'static str {
+	fn into_response(self) -> FutureResponse {
+		with_content_type(Response::new().with_body(self), "text/plain; charset=utf-8")
+	}
+}
+
+impl IntoResponse for Bytes {
+	fn into_response(self) -> FutureResponse {
+		with_content_type(Response::new().with_body(self), "application/octet-stream")
+	}
+}
+
+impl IntoResponse for Vec<u8> {
+	fn into_response(self) -> FutureResponse {
+		with_content_type(Response::new().with_body(self), "application/octet-stream")
+	}
+}
+
+impl IntoResponse for StatusCode {
+	fn into_response(self) -> FutureResponse {
+		Box::new(future::ok(Response::new().with_status(self)))
+	}
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+	fn into_response(self) -> FutureResponse {
+		match Response::new().try_json(&self.0) {
+			Ok(res) => Box::new(future::ok(res)),
+			Err(err) => {
+				error!("Error serializing json response: {}", err);
+				Box::new(future::err(Response::new().internalServerError()))
+			}
+		}
+	}
+}
+
+impl<T: IntoResponse> IntoResponse for (StatusCode, T) {
+	fn into_response(self) -> FutureResponse {
+		let (status, body) = self;
+		map(body.into_response(), move |res| res.with_status(status))
+	}
+}
+
+impl<T: IntoResponse> IntoResponse for (HeaderMap, T) {
+	fn into_response(self) -> FutureResponse {
+		let (headers, body) = self;
+		map(body.into_response(), move |mut res| {
+			res.headers_mut().extend(headers);
+			res
+		})
+	}
+}
+
+impl<T: IntoResponse> IntoResponse for (StatusCode, HeaderMap, T) {
+	fn into_response(self) -> FutureResponse {
+		let (status, headers, body) = self;
+		(status, (headers, body)).into_response()
+	}
+}
+
+impl<T: IntoResponse, E: IntoResponse> IntoResponse for Result<T, E> {
+	fn into_response(self) -> FutureResponse {
+		match self {
+			Ok(value) => value.into_response(),
+			Err(err) => {
+				Box::new(err.into_response().then(|result| {
+					match result {
+						Ok(res) | Err(res) => Err(res),
+					}
+				}))
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::Request;
+	use futures::Stream;
+	use http::request::Builder;
+	use hyper::Body;
+	use proto::ArcService;
+
+	fn HealthService(_req: Request, _res: Response) -> (StatusCode, &'static str) {
+		(StatusCode::ACCEPTED, "OK")
+	}
+
+	fn UserService(_req: Request, _res: Response) -> Result<Json<Vec<&'static str>>, StatusCode> {
+		Err(StatusCode::NOT_FOUND)
+	}
+
+	fn request() -> (Request, Response) {
+		let req: Request = Builder::new()
+			.uri("/users")
+			.body(Body::empty())
+			.unwrap()
+			.into();
+		let res = Response::for_request(&req);
+		(req, res)
+	}
+
+	#[test]
+	fn services_can_return_anything_into_response() {
+		let (req, res) = request();
+		let res = ArcService::call(&HealthService, req, res).wait().unwrap();
+		assert_eq!(res.status(), StatusCode::ACCEPTED);
+		assert_eq!(res.headers()[CONTENT_TYPE], "text/plain; charset=utf-8");
+		// the request is carried over to the new response.
+		assert!(res.request().is_some());
+
+		let (req, res) = request();
+		let res = ArcService::call(&UserService, req, res).wait().unwrap_err();
+		assert_eq!(res.status(), StatusCode::NOT_FOUND);
+	}
+
+	#[test]
+	fn it_serializes_json() {
+		let res = Json(vec!["seun"]).into_response().wait().unwrap();
+		assert_eq!(res.headers()[CONTENT_TYPE], "application/json");
+		let body = res.body().concat2().wait().unwrap();
+		assert_eq!(&body[..], br#"["seun"]"#);
+	}
+}
@@ -41,7 +41,7 @@ pub type MiddleWareFuture<I> = Box<Future<Item = I, Error = Response> + Send>;
 ///
 /// fn main() {
 /// 	let router = Router::new()
-/// 		.get2("/user", mw![hasAccessToken], UserService);
+/// 		.get("/user", Route::new(UserService).before(mw![hasAccessToken]));
 ///   .....
 ///   // start the server mount the routes.
 ///
@@ -85,7 +85,7 @@ pub type MiddleWareFuture<I> = Box<Future<Item = I, Error = Response> + Send>;
 ///
 /// fn main() {
 /// 	let router = Router::new()
-/// 		.get2("/user", mw![middleware1, middleware2, middleware3], TestService); // note that the order of middlewares matter!
+/// 		.get("/user", Route::new(TestService).before(mw![middleware1, middleware2, middleware3])); // note that the order of middlewares matter!
 ///   .....
 ///   // start the server mount the routes.
 ///
@@ -1,5 +1,6 @@
 mod convert;
+mod intoResponse;
 mod middleware;
 mod service;
 
-pub use self::{convert::*, middleware::*, service::*};
+pub use self::{convert::*, intoResponse::*, middleware::*, service::*};
@@ -1,5 +1,7 @@
 use core::{Request, Response};
 use hyper::rt::Future;
+#[cfg(not(feature = "unstable"))]
+use proto::IntoResponse;
 use proto::MiddleWare;
 
 pub type FutureResponse = Box<Future<Item = Response, Error = Response> + Send>;
@@ -7,15 +9,34 @@ pub type FutureResponse = Box<Future<Item = Response, Error = Response> + Send>;
 /// This trait is automatically derived by the #[service] proc_macro.
 pub trait ArcService: ArcServiceClone + Send + Sync {
 	fn call(&self, req: Request, res: Response) -> FutureResponse;
+
+	/// The `Route::body_limit` of this service, the `Router` reads the body
+	/// with it before calling the service.
+	#[doc(hidden)]
+	fn route_body_limit(&self) -> Option<u64> {
+		None
+	}
 }
 
+/// Any function that takes a `Request` and a `Response` and returns something
+/// that implements `IntoResponse` is a service.
 #[cfg(not(feature = "unstable"))]
-impl<T> ArcService for T
+impl<T, R> ArcService for T
 where
-	T: Fn(Request, Response) -> FutureResponse + Send + Sync + Clone + 'static,
+	T: Fn(Request, Response) -> R + Send + Sync + Clone + 'static,
+	R: IntoResponse,
 {
 	fn call(&self, req: Request, res: Response) -> FutureResponse {
-		(self)(req, res)
+		let head = res.request_head();
+		let responseFuture = (self)(req, res).into_response().then(move |result| {
+			// a response built from scratch doesn't carry the request yet.
+			match result {
+				Ok(res) => Ok(res.carry_request(head.as_ref())),
+				Err(res) => Err(res.carry_request(head.as_ref())),
+			}
+		});
+
+		Box::new(responseFuture)
 	}
 }
 
@@ -64,6 +85,12 @@ impl ArcHandler {
 }
 
 impl ArcService for ArcHandler {
+	fn route_body_limit(&self) -> Option<u64> {
+		self.handler
+			.as_ref()
+			.and_then(|handler| handler.route_body_limit())
+	}
+
 	fn call(&self, req: Request, res: Response) -> FutureResponse {
 		if self.before.is_some() && self.after.is_none() {
 			let before = match self.before {
@@ -82,9 +109,10 @@ impl ArcService for ArcHandler {
 				_ => unreachable!(),
 			};
 			let handler = self.handler.clone().unwrap();
+			let head = res.request_head();
 			let responsefuture = handler.call(req, res).then(move |res| {
 				match res {
-					Ok(res) | Err(res) => after.call(res),
+					Ok(res) | Err(res) => after.call(res.carry_request(head.as_ref())),
 				}
 			});
 			return Box::new(responsefuture);
@@ -102,12 +130,15 @@ impl ArcService for ArcHandler {
 				_ => unreachable!(),
 			};
 
+			// responses built from scratch, e.g by a middleware, still get the
+			// request for the response middleware.
+			let head = res.request_head();
 			let responsefuture = before
 				.call(req)
 				.and_then(move |req| handler.call(req, res))
 				.then(move |res| {
 					match res {
-						Ok(res) | Err(res) => after.call(res),
+						Ok(res) | Err(res) => after.call(res.carry_request(head.as_ref())),
 					}
 				});
 			return Box::new(responsefuture);
@@ -1,10 +1,11 @@
 pub mod recognizer;
+mod route;
 mod routegroup;
 mod router;
 mod util;
 
 pub(crate) use self::util::*;
-pub use self::{routegroup::*, router::*};
+pub use self::{route::*, routegroup::*, router::*};
 
 #[cfg(test)]
 #[cfg(feature = "unstable")]
@@ -20,7 +21,7 @@ mod tests {
 	#[service]
 	fn AsyncService(_req: Request, res: Response) {
 		let res = res
-			.with_status(StatusCode::Ok)
+			.with_status(StatusCode::OK)
 			.with_body("Hello World".as_bytes());
 		Result::Ok(res)
 	}
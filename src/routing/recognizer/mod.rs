@@ -1,4 +1,5 @@
 use self::nfa::{CharacterClass, NFA};
+use regex::Regex;
 use std::{
 	cmp::Ordering,
 	collections::{btree_map, BTreeMap},
@@ -10,20 +11,37 @@ pub mod nfa;
 #[derive(Clone)]
 struct Metadata {
 	statics: u32,
+	constrained: u32,
 	dynamics: u32,
 	stars: u32,
 	param_names: Vec<String>,
+	constraints: Vec<Option<Regex>>,
 }
 
 impl Metadata {
 	pub fn new() -> Metadata {
 		Metadata {
 			statics: 0,
+			constrained: 0,
 			dynamics: 0,
 			stars: 0,
 			param_names: Vec::new(),
+			constraints: Vec::new(),
 		}
 	}
+
+	/// Checks the captured params against the constraints of the route.
+	fn satisfied_by(&self, captures: &[&str]) -> bool {
+		self.constraints
+			.iter()
+			.zip(captures)
+			.all(|(constraint, capture)| {
+				match *constraint {
+					Some(ref regex) => regex.is_match(capture),
+					None => true,
+				}
+			})
+	}
 }
 
 impl Ord for Metadata {
@@ -32,6 +50,10 @@ impl Ord for Metadata {
 			Ordering::Greater
 		} else if self.statics < other.statics {
 			Ordering::Less
+		} else if self.constrained > other.constrained {
+			Ordering::Greater
+		} else if self.constrained < other.constrained {
+			Ordering::Less
 		} else if self.dynamics > other.dynamics {
 			Ordering::Greater
 		} else if self.dynamics < other.dynamics {
@@ -55,6 +77,7 @@ impl PartialOrd for Metadata {
 impl PartialEq for Metadata {
 	fn eq(&self, other: &Metadata) -> bool {
 		self.statics == other.statics
+			&& self.constrained == other.constrained
 			&& self.dynamics == other.dynamics
 			&& self.stars == other.stars
 	}
@@ -146,6 +169,24 @@ impl<T> Router<T> {
 		}
 	}
 
+	/// Adds a route to the recognizer.
+	///
+	/// Besides static segments, a route can contain `:dynamic` params, which
+	/// match anything up to the next `/`, and `*star` params, which match the
+	/// rest of the path. A dynamic param can be constrained by a regex e.g
+	/// `/users/:id(\d+)`, paths that don't satisfy the constraint fall
+	/// through to the next matching route.
+	///
+	/// A param starts at the beginning of a segment or right after a `.` or
+	/// `-`, so a segment can hold more than one, e.g `/files/:name.:ext`.
+	/// Anywhere else `:` and `*` are matched as is, e.g `/users:batchGet`.
+	/// A param that follows a separator can't contain it, so `:name.:ext`
+	/// splits `archive.tar.gz` at the last `.`.
+	///
+	/// # Panics
+	///
+	/// If the regex for a constraint is invalid, its parentheses are
+	/// unbalanced or it contains a `/`.
 	pub fn add(&mut self, mut route: &str, dest: T) {
 		if route.len() != 0 && route.as_bytes()[0] == b'/' {
 			route = &route[1..];
@@ -155,23 +196,51 @@ impl<T> Router<T> {
 		let mut state = 0;
 		let mut metadata = Metadata::new();
 
-		for (i, segment) in route.split('/').enumerate() {
+		for (i, segment) in split_segments(route).into_iter().enumerate() {
 			if i > 0 {
 				state = nfa.put(state, CharacterClass::valid_char('/'));
 			}
 
-			if segment.len() > 0 && segment.as_bytes()[0] == b':' {
-				state = process_dynamic_segment(nfa, state);
-				metadata.dynamics += 1;
-				metadata.param_names.push(segment[1..].to_string());
-			} else if segment.len() > 0 && segment.as_bytes()[0] == b'*' {
-				state = process_star_state(nfa, state);
-				metadata.stars += 1;
-				metadata.param_names.push(segment[1..].to_string());
-			} else {
+			let pieces = parse_segment(segment)
+				.unwrap_or_else(|err| panic!("Invalid route {}: {}", route, err));
+			if pieces.is_empty() {
 				state = process_static_segment(segment, nfa, state);
 				metadata.statics += 1;
 			}
+
+			// the separator the next param follows, if any.
+			let mut separator = None;
+			for piece in pieces {
+				match piece {
+					Piece::Static(text) => {
+						state = process_static_segment(text, nfa, state);
+						metadata.statics += 1;
+						separator = text.chars().last();
+					}
+					Piece::Dynamic(name, None) => {
+						state = process_dynamic_segment(nfa, state, separator.take());
+						metadata.dynamics += 1;
+						metadata.param_names.push(name.to_string());
+						metadata.constraints.push(None);
+					}
+					Piece::Dynamic(name, Some(pattern)) => {
+						let regex = Regex::new(&format!("^(?:{})$", pattern)).unwrap_or_else(|err| {
+							panic!("Invalid constraint for param {} in {}: {}", name, route, err)
+						});
+						state = process_constrained_segment(nfa, state, separator.take());
+						metadata.dynamics += 1;
+						metadata.constrained += 1;
+						metadata.param_names.push(name.to_string());
+						metadata.constraints.push(Some(regex));
+					}
+					Piece::Star(name) => {
+						state = process_star_state(nfa, state);
+						metadata.stars += 1;
+						metadata.param_names.push(name.to_string());
+						metadata.constraints.push(None);
+					}
+				}
+			}
 		}
 
 		nfa.acceptance(state);
@@ -185,27 +254,153 @@ impl<T> Router<T> {
 		}
 
 		let nfa = &self.nfa;
-		let result = nfa.process(path, |index| nfa.get(index).metadata.as_ref().unwrap());
+		let matches = nfa.process_all(path, |index| nfa.get(index).metadata.as_ref().unwrap())?;
 
-		match result {
-			Ok(nfa_match) => {
-				let mut map = Params::new();
-				let state = &nfa.get(nfa_match.state);
-				let metadata = state.metadata.as_ref().unwrap();
-				let param_names = metadata.param_names.clone();
+		for nfa_match in matches {
+			let state = &nfa.get(nfa_match.state);
+			let metadata = state.metadata.as_ref().unwrap();
 
-				for (i, capture) in nfa_match.captures.iter().enumerate() {
-					map.insert(param_names[i].to_string(), capture.to_string());
-				}
+			if !metadata.satisfied_by(&nfa_match.captures) {
+				continue;
+			}
 
-				let handler = self.handlers.get(&nfa_match.state).unwrap();
-				Ok(Match::new(handler, map))
+			let mut map = Params::new();
+			for (i, capture) in nfa_match.captures.iter().enumerate() {
+				map.insert(metadata.param_names[i].to_string(), capture.to_string());
 			}
-			Err(str) => Err(str),
+
+			let handler = self.handlers.get(&nfa_match.state).unwrap();
+			return Ok(Match::new(handler, map));
 		}
+
+		Err(format!("No route satisfied the constraints for {}", path))
 	}
 }
 
+/// A part of a route segment.
+#[derive(Debug, PartialEq)]
+enum Piece<'a> {
+	Static(&'a str),
+	/// A `:dynamic` param and its optional regex constraint.
+	Dynamic(&'a str, Option<&'a str>),
+	Star(&'a str),
+}
+
+/// Splits the route on `/`, except inside the parentheses of a constraint,
+/// so that a `/` in a constraint is reported instead of breaking the route.
+fn split_segments(route: &str) -> Vec<&str> {
+	let mut segments = Vec::new();
+	let mut depth = 0;
+	let mut escaped = false;
+	let mut start = 0;
+
+	for (i, byte) in route.bytes().enumerate() {
+		if escaped {
+			escaped = false;
+			continue;
+		}
+
+		match byte {
+			b'\\' => escaped = true,
+			b'(' => depth += 1,
+			b')' if depth > 0 => depth -= 1,
+			b'/' if depth == 0 => {
+				segments.push(&route[start..i]);
+				start = i + 1;
+			}
+			_ => {}
+		}
+	}
+	segments.push(&route[start..]);
+
+	segments
+}
+
+fn is_param_char(byte: u8) -> bool {
+	byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+/// The characters a param can follow within a segment.
+const SEPARATORS: &[u8] = b".-";
+
+fn parse_segment(segment: &str) -> Result<Vec<Piece>, String> {
+	let bytes = segment.as_bytes();
+	let mut pieces = Vec::new();
+	let mut start = 0;
+	let mut i = 0;
+
+	while i < bytes.len() {
+		let is_param = (bytes[i] == b':' || bytes[i] == b'*')
+			&& (i == 0 || SEPARATORS.contains(&bytes[i - 1]));
+		if !is_param {
+			i += 1;
+			continue;
+		}
+
+		if start < i {
+			pieces.push(Piece::Static(&segment[start..i]));
+		}
+
+		let kind = bytes[i];
+		i += 1;
+		let name_start = i;
+		while i < bytes.len() && is_param_char(bytes[i]) {
+			i += 1;
+		}
+		let name = &segment[name_start..i];
+
+		if kind == b'*' {
+			pieces.push(Piece::Star(name));
+		} else if i < bytes.len() && bytes[i] == b'(' {
+			let pattern_start = i + 1;
+			let mut depth = 0;
+			let mut escaped = false;
+			while i < bytes.len() {
+				match bytes[i] {
+					_ if escaped => escaped = false,
+					b'\\' => escaped = true,
+					b'(' => depth += 1,
+					b')' => {
+						depth -= 1;
+						if depth == 0 {
+							break;
+						}
+					}
+					_ => {}
+				}
+				i += 1;
+			}
+			if i == bytes.len() {
+				return Err(format!(
+					"unbalanced parentheses in the constraint for param {}",
+					name
+				));
+			}
+			let pattern = &segment[pattern_start..i];
+			if pattern.contains('/') {
+				return Err(format!(
+					"the constraint for param {} contains a `/`, params never match across \
+					 segments",
+					name
+				));
+			}
+			pieces.push(Piece::Dynamic(name, Some(pattern)));
+			// skip the closing parenthesis.
+			i += 1;
+		} else {
+			pieces.push(Piece::Dynamic(name, None));
+		}
+
+		start = i;
+	}
+
+	if start < bytes.len() {
+		pieces.push(Piece::Static(&segment[start..]));
+	}
+
+	Ok(pieces)
+}
+
 fn process_static_segment<T>(segment: &str, nfa: &mut NFA<T>, mut state: usize) -> usize {
 	for char in segment.chars() {
 		state = nfa.put(state, CharacterClass::valid_char(char));
@@ -214,8 +409,36 @@ fn process_static_segment<T>(segment: &str, nfa: &mut NFA<T>, mut state: usize)
 	state
 }
 
-fn process_dynamic_segment<T>(nfa: &mut NFA<T>, mut state: usize) -> usize {
-	state = nfa.put(state, CharacterClass::invalid_char('/'));
+/// The characters a dynamic param matches, a param that follows a separator
+/// can't contain it.
+fn param_chars(separator: Option<char>) -> CharacterClass {
+	match separator {
+		Some(separator) => CharacterClass::invalid(&format!("/{}", separator)),
+		None => CharacterClass::invalid_char('/'),
+	}
+}
+
+fn process_dynamic_segment<T>(
+	nfa: &mut NFA<T>,
+	mut state: usize,
+	separator: Option<char>,
+) -> usize {
+	state = nfa.put(state, param_chars(separator));
+	nfa.put_state(state, state);
+	nfa.start_capture(state);
+	nfa.end_capture(state);
+
+	state
+}
+
+/// Constrained params get a state of their own, so they don't share it with
+/// other params at the same position that have a different constraint.
+fn process_constrained_segment<T>(
+	nfa: &mut NFA<T>,
+	mut state: usize,
+	separator: Option<char>,
+) -> usize {
+	state = nfa.put_new(state, param_chars(separator));
 	nfa.put_state(state, state);
 	nfa.start_capture(state);
 	nfa.end_capture(state);
@@ -339,6 +562,110 @@ fn star() {
 	assert_eq!(m.params, params("foo", "bazz"));
 }
 
+#[test]
+fn constrained_params() {
+	let mut router = Router::new();
+
+	router.add("/users/:id(\\d+)", "id".to_string());
+	router.add("/users/:name", "name".to_string());
+
+	let id = router.recognize("/users/42").unwrap();
+	assert_eq!(*id.handler, "id".to_string());
+	assert_eq!(id.params, params("id", "42"));
+
+	let name = router.recognize("/users/new").unwrap();
+	assert_eq!(*name.handler, "name".to_string());
+	assert_eq!(name.params, params("name", "new"));
+}
+
+#[test]
+fn constrained_params_without_fallback() {
+	let mut router = Router::new();
+
+	router.add("/posts/:id([0-9]{2})/comments", "comments".to_string());
+
+	assert!(router.recognize("/posts/12/comments").is_ok());
+	assert!(router.recognize("/posts/123/comments").is_err());
+	assert!(router.recognize("/posts/ab/comments").is_err());
+}
+
+#[test]
+fn multiple_params_in_a_segment() {
+	let mut router = Router::new();
+
+	router.add("/files/:name.:ext", "file".to_string());
+
+	let m = router.recognize("/files/report.pdf").unwrap();
+	assert_eq!(*m.handler, "file".to_string());
+	assert_eq!(m.params, two_params("name", "report", "ext", "pdf"));
+}
+
+#[test]
+fn multiple_params_split_at_the_last_separator() {
+	let mut router = Router::new();
+
+	router.add("/files/:name.:ext", "file".to_string());
+	router.add("/dates/:year-:month-:day", "date".to_string());
+
+	let m = router.recognize("/files/archive.tar.gz").unwrap();
+	assert_eq!(m.params, two_params("name", "archive.tar", "ext", "gz"));
+
+	let m = router.recognize("/dates/2018-07-01").unwrap();
+	assert_eq!(m.params["year"], "2018");
+	assert_eq!(m.params["month"], "07");
+	assert_eq!(m.params["day"], "01");
+}
+
+#[test]
+fn colons_inside_a_segment_are_static() {
+	let mut router = Router::new();
+
+	router.add("/v1/users:batchGet", "batch".to_string());
+	router.add("/time/12:30", "time".to_string());
+
+	let m = router.recognize("/v1/users:batchGet").unwrap();
+	assert_eq!(*m.handler, "batch".to_string());
+	assert_eq!(m.params, Params::new());
+	assert!(router.recognize("/v1/usersbatchGet").is_err());
+	assert!(router.recognize("/time/12:30").is_ok());
+	assert!(router.recognize("/time/12:45").is_err());
+}
+
+#[test]
+#[should_panic(expected = "unbalanced parentheses")]
+fn unbalanced_constraints_panic() {
+	let mut router = Router::new();
+	router.add("/users/:id(\\d+", "id".to_string());
+}
+
+#[test]
+fn segments_are_parsed_into_pieces() {
+	assert_eq!(
+		parse_segment(":name.:ext").unwrap(),
+		vec![
+			Piece::Dynamic("name", None),
+			Piece::Static("."),
+			Piece::Dynamic("ext", None),
+		]
+	);
+	assert_eq!(
+		parse_segment(":id(\\d+)").unwrap(),
+		vec![Piece::Dynamic("id", Some("\\d+"))]
+	);
+	assert_eq!(
+		parse_segment("users:batchGet").unwrap(),
+		vec![Piece::Static("users:batchGet")]
+	);
+	assert!(parse_segment(":id((\\d+)").is_err());
+}
+
+#[test]
+#[should_panic(expected = "the constraint for param id contains a `/`")]
+fn constraints_with_slashes_panic() {
+	let mut router = Router::new();
+	router.add("/users/:id(\\d+/\\d+)/edit", "id".to_string());
+}
+
 #[allow(dead_code)]
 fn params(key: &str, val: &str) -> Params {
 	let mut map = Params::new();
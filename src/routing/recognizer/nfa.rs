@@ -165,6 +165,8 @@ pub struct State<T> {
 	pub acceptance: bool,
 	pub start_capture: bool,
 	pub end_capture: bool,
+	/// Created by `put_new`, `put` never reuses it for another route.
+	pub exclusive: bool,
 	pub metadata: Option<T>,
 }
 
@@ -183,6 +185,7 @@ impl<T> State<T> {
 			acceptance: false,
 			start_capture: false,
 			end_capture: false,
+			exclusive: false,
 			metadata: None,
 		}
 	}
@@ -223,23 +226,10 @@ impl<T> NFA<T> {
 		I: Ord,
 		F: FnMut(usize) -> I,
 	{
-		let mut threads = vec![Thread::new()];
-
-		for (i, char) in string.chars().enumerate() {
-			let next_threads = self.process_char(threads, char, i);
-
-			if next_threads.is_empty() {
-				return Err(format!("Couldn't process {}", string));
-			}
-
-			threads = next_threads;
-		}
-
-		let returned = threads
-			.into_iter()
-			.filter(|thread| self.get(thread.state).acceptance);
+		let returned = self.accepted_threads(string)?;
 
 		let thread = returned
+			.into_iter()
 			.fold(None, |prev, y| {
 				let y_v = ord(y.state);
 				match prev {
@@ -261,14 +251,66 @@ impl<T> NFA<T> {
 				     acceptance state"
 					.to_string())
 			}
-			Some(mut thread) => {
-				if thread.capture_begin.is_some() {
-					thread.end_capture(string.len());
-				}
-				let state = self.get(thread.state);
-				Ok(Match::new(state.index, thread.extract(string)))
+			Some(thread) => Ok(self.to_match(thread, string)),
+		}
+	}
+
+	/// Like `process`, but returns every match, best match first.
+	pub fn process_all<'a, I, F>(
+		&self,
+		string: &'a str,
+		mut ord: F,
+	) -> Result<Vec<Match<'a>>, String>
+	where
+		I: Ord,
+		F: FnMut(usize) -> I,
+	{
+		let mut returned = self
+			.accepted_threads(string)?
+			.into_iter()
+			.map(|thread| (ord(thread.state), thread))
+			.collect::<Vec<_>>();
+
+		if returned.is_empty() {
+			return Err("The string was exhausted before reaching an \
+			            acceptance state"
+				.to_string());
+		}
+
+		// the sort is stable, so matches with an equal ranking keep their order.
+		returned.sort_by(|&(ref x_v, _), &(ref y_v, _)| y_v.cmp(x_v));
+
+		Ok(returned
+			.into_iter()
+			.map(|(_, thread)| self.to_match(thread, string))
+			.collect())
+	}
+
+	fn accepted_threads(&self, string: &str) -> Result<Vec<Thread>, String> {
+		let mut threads = vec![Thread::new()];
+
+		for (i, char) in string.chars().enumerate() {
+			let next_threads = self.process_char(threads, char, i);
+
+			if next_threads.is_empty() {
+				return Err(format!("Couldn't process {}", string));
 			}
+
+			threads = next_threads;
+		}
+
+		Ok(threads
+			.into_iter()
+			.filter(|thread| self.get(thread.state).acceptance)
+			.collect())
+	}
+
+	fn to_match<'a>(&self, mut thread: Thread, string: &'a str) -> Match<'a> {
+		if thread.capture_begin.is_some() {
+			thread.end_capture(string.len());
 		}
+		let state = self.get(thread.state);
+		Match::new(state.index, thread.extract(string))
 	}
 
 	#[inline]
@@ -325,7 +367,7 @@ impl<T> NFA<T> {
 
 			for &index in state.next_states.iter() {
 				let state = self.get(index);
-				if state.chars == chars {
+				if !state.exclusive && state.chars == chars {
 					return index;
 				}
 			}
@@ -336,6 +378,16 @@ impl<T> NFA<T> {
 		state
 	}
 
+	/// Like `put`, but always creates a new state, instead of reusing a child
+	/// state that matches the same characters. Later calls to `put` don't
+	/// reuse it either.
+	pub fn put_new(&mut self, index: usize, chars: CharacterClass) -> usize {
+		let state = self.new_state(chars);
+		self.get_mut(state).exclusive = true;
+		self.get_mut(index).next_states.push(state);
+		state
+	}
+
 	pub fn put_state(&mut self, index: usize, child: usize) {
 		if !self.states[index].next_states.contains(&child) {
 			self.get_mut(index).next_states.push(child);
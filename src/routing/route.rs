@@ -0,0 +1,145 @@
+use contrib::apply_body_limit;
+use core::{Request, Response};
+use futures::future;
+use proto::{ArcHandler, ArcService, FutureResponse, MiddleWare};
+
+/// A Service along with the middlewares that only apply to it.
+///
+/// Since `Route` is itself an `ArcService`, it can be registered with any of
+/// the route methods on `Router` and `RouteGroup`.
+///
+/// ```rust, ignore
+/// let router = Router::new()
+/// 	.get("/profile", Route::new(ProfileService).before(mw![isAuth]))
+/// 	.post(
+/// 		"/profile",
+/// 		Route::new(UpdateProfileService)
+/// 			.before(mw![isAuth])
+/// 			.after(mw![logger]),
+/// 	);
+/// ```
+#[derive(Clone)]
+pub struct Route {
+	handler: ArcHandler,
+	body_limit: Option<u64>,
+}
+
+impl Route {
+	/// Create a new route for the Service.
+	pub fn new<S>(handler: S) -> Self
+	where
+		S: ArcService + 'static,
+	{
+		Self {
+			handler: ArcHandler::new(handler),
+			body_limit: None,
+		}
+	}
+
+	/// Mount a request middleware on this route, it runs after the ones
+	/// mounted before it.
+	pub fn before<M>(mut self, before: M) -> Self
+	where
+		M: MiddleWare<Request> + 'static,
+	{
+		self.handler.before = match self.handler.before.take() {
+			Some(mounted) => Some(mw![mounted, before]),
+			None => Some(Box::new(before)),
+		};
+
+		self
+	}
+
+	/// Mount a response middleware on this route, it runs after the ones
+	/// mounted before it.
+	pub fn after<M>(mut self, after: M) -> Self
+	where
+		M: MiddleWare<Response> + 'static,
+	{
+		self.handler.after = match self.handler.after.take() {
+			Some(mounted) => Some(mw![mounted, after]),
+			None => Some(Box::new(after)),
+		};
+
+		self
+	}
+
+	/// The most bytes the `BodyParser` buffers for this route, see `BodyLimit`.
+	pub fn body_limit(mut self, bytes: u64) -> Self {
+		self.body_limit = Some(bytes);
+
+		self
+	}
+}
+
+impl ArcService for Route {
+	fn route_body_limit(&self) -> Option<u64> {
+		self.body_limit.or_else(|| self.handler.route_body_limit())
+	}
+
+	fn call(&self, mut req: Request, res: Response) -> FutureResponse {
+		if let Some(limit) = self.body_limit {
+			if let Err(res) = apply_body_limit(&mut req, limit) {
+				return Box::new(future::err(res));
+			}
+		}
+
+		self.handler.call(req, res)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::{Future, Stream};
+	use http::request::Builder;
+	use hyper::{header::HeaderValue, Body};
+	use proto::MiddleWareFuture;
+
+	fn TraceService(req: Request, _res: Response) -> String {
+		req.headers()
+			.get_all("x-trace")
+			.iter()
+			.filter_map(|value| value.to_str().ok())
+			.collect::<Vec<_>>()
+			.join(",")
+	}
+
+	fn traceRequest(
+		name: &'static str,
+	) -> impl Fn(Request) -> MiddleWareFuture<Request> + Clone + Send + Sync + 'static {
+		move |mut req: Request| -> MiddleWareFuture<Request> {
+			req.headers_mut()
+				.append("x-trace", HeaderValue::from_static(name));
+			Box::new(future::ok(req))
+		}
+	}
+
+	fn traceResponse(
+		name: &'static str,
+	) -> impl Fn(Response) -> MiddleWareFuture<Response> + Clone + Send + Sync + 'static {
+		move |mut res: Response| -> MiddleWareFuture<Response> {
+			res.headers_mut()
+				.append("x-trace", HeaderValue::from_static(name));
+			Box::new(future::ok(res))
+		}
+	}
+
+	#[test]
+	fn middlewares_run_in_the_order_they_were_mounted() {
+		let route = Route::new(TraceService)
+			.before(traceRequest("first"))
+			.before(traceRequest("second"))
+			.after(traceResponse("first"))
+			.after(traceResponse("second"));
+
+		let req: Request = Builder::new().uri("/").body(Body::empty()).unwrap().into();
+		let res = Response::for_request(&req);
+		let res = route.call(req, res).wait().unwrap();
+
+		let trace = res.headers().get_all("x-trace").iter().collect::<Vec<_>>();
+		assert_eq!(trace, vec!["first", "second"]);
+		let body = res.body().concat2().wait().unwrap();
+		assert_eq!(&body[..], b"first,second");
+	}
+}
@@ -1,6 +1,6 @@
 use core::{Request, Response};
 use proto::{ArcHandler, ArcService, MiddleWare};
-use routing::stripTrailingSlash;
+use routing::{stripTrailingSlash, METHODS};
 
 use hyper::Method;
 use std::collections::HashMap;
@@ -101,6 +101,10 @@ impl RouteGroup {
 		self.route(Method::GET, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn get2<S, M>(self, route: &'static str, before: M, handler: S) -> Self
 	where
 		S: ArcService + 'static,
@@ -114,6 +118,10 @@ impl RouteGroup {
 		self.route(Method::GET, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn get3<S, ReqMW, ResMW>(
 		self,
 		route: &'static str,
@@ -134,6 +142,10 @@ impl RouteGroup {
 		self.route(Method::GET, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn get4<S, M>(self, route: &'static str, handler: S, after: M) -> Self
 	where
 		S: ArcService + 'static,
@@ -157,6 +169,10 @@ impl RouteGroup {
 
 	/// mount a Service as well as a MiddleWare<Request>
 	/// for a POST request
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn post2<S, M>(self, route: &'static str, before: M, handler: S) -> Self
 	where
 		S: ArcService + 'static,
@@ -172,6 +188,10 @@ impl RouteGroup {
 
 	/// mount a Service as well as a MiddleWare<Request> and
 	/// MiddleWare<Response> for a POST request
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn post3<S, ReqMW, ResMW>(
 		self,
 		route: &'static str,
@@ -194,6 +214,10 @@ impl RouteGroup {
 
 	/// mount a Service as well as a MiddleWare<Response>
 	/// for a POST request
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn post4<S, M>(self, route: &'static str, handler: S, after: M) -> Self
 	where
 		S: ArcService + 'static,
@@ -204,7 +228,7 @@ impl RouteGroup {
 			handler: Some(Box::new(handler)),
 			after: Some(Box::new(after)),
 		};
-		self.route(Method::GET, route, handler)
+		self.route(Method::POST, route, handler)
 	}
 
 	/// add a route and a ServiceHandler for a put request
@@ -215,6 +239,10 @@ impl RouteGroup {
 		self.route(Method::PUT, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn put2<S, M>(self, route: &'static str, before: M, handler: S) -> Self
 	where
 		S: ArcService + 'static,
@@ -228,6 +256,10 @@ impl RouteGroup {
 		self.route(Method::PUT, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn put3<S, ReqMW, ResMW>(
 		self,
 		route: &'static str,
@@ -248,6 +280,10 @@ impl RouteGroup {
 		self.route(Method::PUT, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn put4<S, M>(self, route: &'static str, handler: S, after: M) -> Self
 	where
 		S: ArcService + 'static,
@@ -269,6 +305,10 @@ impl RouteGroup {
 		self.route(Method::PATCH, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn patch2<S, M>(self, route: &'static str, before: M, handler: S) -> Self
 	where
 		S: ArcService + 'static,
@@ -282,6 +322,10 @@ impl RouteGroup {
 		self.route(Method::PATCH, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn patch3<S, ReqMW, ResMW>(
 		self,
 		route: &'static str,
@@ -302,6 +346,10 @@ impl RouteGroup {
 		self.route(Method::PATCH, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn patch4<S, M>(self, route: &'static str, handler: S, after: M) -> Self
 	where
 		S: ArcService + 'static,
@@ -323,6 +371,10 @@ impl RouteGroup {
 		self.route(Method::DELETE, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn delete2<S, M>(self, route: &'static str, before: M, handler: S) -> Self
 	where
 		S: ArcService + 'static,
@@ -336,6 +388,10 @@ impl RouteGroup {
 		self.route(Method::DELETE, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn delete3<S, ReqMW, ResMW>(
 		self,
 		route: &'static str,
@@ -356,6 +412,10 @@ impl RouteGroup {
 		self.route(Method::DELETE, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn delete4<S, M>(self, route: &'static str, handler: S, after: M) -> Self
 	where
 		S: ArcService + 'static,
@@ -369,7 +429,47 @@ impl RouteGroup {
 		self.route(Method::DELETE, route, handler)
 	}
 
-	fn route<T: ToString, S: ArcService + 'static + Send + Sync>(
+	/// Add a route and a ServiceHandler for a HEAD request.
+	///
+	/// This overrides the default behaviour of running the GET handler for
+	/// the route and stripping the body of its response.
+	pub fn head<S>(self, route: &'static str, handler: S) -> Self
+	where
+		S: ArcService + 'static,
+	{
+		self.route(Method::HEAD, route, handler)
+	}
+
+	/// Add a route and a ServiceHandler for an OPTIONS request.
+	///
+	/// This overrides the default response, which lists the methods
+	/// registered for the route in the `Allow` header.
+	pub fn options<S>(self, route: &'static str, handler: S) -> Self
+	where
+		S: ArcService + 'static,
+	{
+		self.route(Method::OPTIONS, route, handler)
+	}
+
+	/// Add a route and a ServiceHandler for all the standard HTTP methods.
+	///
+	/// Non-standard methods (e.g `PROPFIND`) still get a 405, register them
+	/// with `route`.
+	pub fn any<S>(mut self, route: &'static str, handler: S) -> Self
+	where
+		S: ArcService + 'static,
+	{
+		let handler = ArcHandler::new(handler);
+		for method in METHODS.iter() {
+			self = self.route(method.clone(), route, handler.clone());
+		}
+
+		self
+	}
+
+	/// Add a route and a ServiceHandler for any method, including non-standard
+	/// ones.
+	pub fn route<T: ToString, S: ArcService + 'static + Send + Sync>(
 		mut self,
 		method: Method,
 		path: T,
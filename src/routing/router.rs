@@ -1,10 +1,16 @@
 use super::recognizer::{Match, Router as Recognizer};
-use core::{Request, Response};
-use futures::IntoFuture;
-use hyper::Method;
+use contrib::{apply_body_limit, BeforeRouting, BodyParser, DeferredBody};
+use core::{Request, RequestHead, Response};
+use futures::{future, Future, IntoFuture};
+use hyper::{
+	body::Payload,
+	header::{HeaderValue, ALLOW, CONTENT_LENGTH},
+	Body,
+	Method,
+};
 use proto::{ArcHandler, ArcService, FutureResponse, MiddleWare};
-use routing::{stripTrailingSlash, RouteGroup};
-use std::collections::HashMap;
+use routing::{stripTrailingSlash, RouteGroup, METHODS};
+use std::{collections::HashMap, sync::Arc};
 
 /// The main router of you application that is supplied to the ArcReactor.
 ///
@@ -14,6 +20,8 @@ pub struct Router {
 	pub(crate) before: Option<Box<MiddleWare<Request>>>,
 	pub(crate) after: Option<Box<MiddleWare<Response>>>,
 	pub(crate) notFound: Option<Box<ArcService>>,
+	pub(crate) methodNotAllowed: Option<Box<ArcService>>,
+	pub(crate) body_limit: Option<u64>,
 }
 
 impl Router {
@@ -24,6 +32,8 @@ impl Router {
 			routes: HashMap::new(),
 			after: None,
 			notFound: None,
+			methodNotAllowed: None,
+			body_limit: None,
 		}
 	}
 
@@ -78,13 +88,22 @@ impl Router {
 	///
 	/// Ensure that the request middleware is added before any routes on the
 	/// router. The middleware only applies to the routes that are added after
-	/// it has been mounted.
+	/// it has been mounted, and to the responses the router builds itself,
+	/// i.e 404s, 405s and the answers to OPTIONS requests.
 	pub fn after<T: 'static + MiddleWare<Response>>(mut self, after: T) -> Self {
 		self.after = Some(Box::new(after));
 
 		self
 	}
 
+	/// The most bytes the `BodyParser` buffers for the routes on this router,
+	/// see `BodyLimit`.
+	pub fn body_limit(mut self, bytes: u64) -> Self {
+		self.body_limit = Some(bytes);
+
+		self
+	}
+
 	/// Add a route and a ServiceHandler for a GET request.
 	pub fn get<S>(self, route: &'static str, handler: S) -> Self
 	where
@@ -93,6 +112,10 @@ impl Router {
 		self.route(Method::GET, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn get2<S, M>(self, route: &'static str, before: M, handler: S) -> Self
 	where
 		S: ArcService + 'static,
@@ -106,6 +129,10 @@ impl Router {
 		self.route(Method::GET, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn get3<S, ReqMW, ResMW>(
 		self,
 		route: &'static str,
@@ -126,6 +153,10 @@ impl Router {
 		self.route(Method::GET, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn get4<S, M>(self, route: &'static str, handler: S, after: M) -> Self
 	where
 		S: ArcService + 'static,
@@ -149,6 +180,10 @@ impl Router {
 
 	/// mount a Service as well as a MiddleWare<Request>
 	/// for a POST request
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn post2<S, M>(self, route: &'static str, before: M, handler: S) -> Self
 	where
 		S: ArcService + 'static,
@@ -164,6 +199,10 @@ impl Router {
 
 	/// mount a Service as well as a MiddleWare<Request> and
 	/// MiddleWare<Response> for a POST request
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn post3<S, ReqMW, ResMW>(
 		self,
 		route: &'static str,
@@ -186,6 +225,10 @@ impl Router {
 
 	/// mount a Service as well as a MiddleWare<Response>
 	/// for a POST request
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn post4<S, M>(self, route: &'static str, handler: S, after: M) -> Self
 	where
 		S: ArcService + 'static,
@@ -196,7 +239,7 @@ impl Router {
 			handler: Some(Box::new(handler)),
 			after: Some(Box::new(after)),
 		};
-		self.route(Method::GET, route, handler)
+		self.route(Method::POST, route, handler)
 	}
 
 	/// add a route and a ServiceHandler for a put request
@@ -207,6 +250,10 @@ impl Router {
 		self.route(Method::PUT, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn put2<S, M>(self, route: &'static str, before: M, handler: S) -> Self
 	where
 		S: ArcService + 'static,
@@ -220,6 +267,10 @@ impl Router {
 		self.route(Method::PUT, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn put3<S, ReqMW, ResMW>(
 		self,
 		route: &'static str,
@@ -240,6 +291,10 @@ impl Router {
 		self.route(Method::PUT, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn put4<S, M>(self, route: &'static str, handler: S, after: M) -> Self
 	where
 		S: ArcService + 'static,
@@ -261,6 +316,10 @@ impl Router {
 		self.route(Method::PATCH, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn patch2<S, M>(self, route: &'static str, before: M, handler: S) -> Self
 	where
 		S: ArcService + 'static,
@@ -274,6 +333,10 @@ impl Router {
 		self.route(Method::PATCH, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn patch3<S, ReqMW, ResMW>(
 		self,
 		route: &'static str,
@@ -294,6 +357,10 @@ impl Router {
 		self.route(Method::PATCH, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn patch4<S, M>(self, route: &'static str, handler: S, after: M) -> Self
 	where
 		S: ArcService + 'static,
@@ -315,6 +382,10 @@ impl Router {
 		self.route(Method::DELETE, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn delete2<S, M>(self, route: &'static str, before: M, handler: S) -> Self
 	where
 		S: ArcService + 'static,
@@ -328,6 +399,10 @@ impl Router {
 		self.route(Method::DELETE, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn delete3<S, ReqMW, ResMW>(
 		self,
 		route: &'static str,
@@ -348,6 +423,10 @@ impl Router {
 		self.route(Method::DELETE, route, handler)
 	}
 
+	#[deprecated(
+		since = "0.3.0",
+		note = "use `Route::new(handler).before(..).after(..)` instead"
+	)]
 	pub fn delete4<S, M>(self, route: &'static str, handler: S, after: M) -> Self
 	where
 		S: ArcService + 'static,
@@ -361,6 +440,28 @@ impl Router {
 		self.route(Method::DELETE, route, handler)
 	}
 
+	/// Add a route and a ServiceHandler for a HEAD request.
+	///
+	/// This overrides the default behaviour of running the GET handler for
+	/// the route and stripping the body of its response.
+	pub fn head<S>(self, route: &'static str, handler: S) -> Self
+	where
+		S: ArcService + 'static,
+	{
+		self.route(Method::HEAD, route, handler)
+	}
+
+	/// Add a route and a ServiceHandler for an OPTIONS request.
+	///
+	/// This overrides the default response, which lists the methods
+	/// registered for the route in the `Allow` header.
+	pub fn options<S>(self, route: &'static str, handler: S) -> Self
+	where
+		S: ArcService + 'static,
+	{
+		self.route(Method::OPTIONS, route, handler)
+	}
+
 	/// Add a 404 handler.
 	pub fn notFound<S>(mut self, handler: S) -> Self
 	where
@@ -371,7 +472,44 @@ impl Router {
 		self
 	}
 
-	fn route<S>(mut self, method: Method, path: &'static str, handler: S) -> Self
+	/// Add a 405 handler.
+	///
+	/// It is called when the path matches a route registered for other
+	/// methods, the response it receives already has the 405 status and the
+	/// `Allow` header set.
+	pub fn methodNotAllowed<S>(mut self, handler: S) -> Self
+	where
+		S: ArcService + 'static,
+	{
+		self.methodNotAllowed = Some(Box::new(handler));
+
+		self
+	}
+
+	/// Add a route and a ServiceHandler for all the standard HTTP methods.
+	///
+	/// Non-standard methods (e.g `PROPFIND`) still get a 405, register them
+	/// with `route`.
+	pub fn any<S>(mut self, route: &'static str, handler: S) -> Self
+	where
+		S: ArcService + 'static,
+	{
+		let handler = ArcHandler::new(handler);
+		for method in METHODS.iter() {
+			self = self.route(method.clone(), route, handler.clone());
+		}
+
+		self
+	}
+
+	/// Add a route and a ServiceHandler for any method, including non-standard
+	/// ones.
+	///
+	/// ```rust, ignore
+	/// let propfind = Method::from_bytes(b"PROPFIND").unwrap();
+	/// let router = Router::new().route(propfind, "/files/*path", PropFindService);
+	/// ```
+	pub fn route<S>(mut self, method: Method, path: &'static str, handler: S) -> Self
 	where
 		S: ArcService + 'static,
 	{
@@ -399,26 +537,297 @@ impl Router {
 			.get(method)
 			.and_then(|recognizer| recognizer.recognize(route).ok())
 	}
+
+	/// Returns the methods that have a route matching the path, sorted.
+	///
+	/// HEAD is included when there's a GET route, and OPTIONS whenever any
+	/// route matches, since the router answers those on its own.
+	pub(crate) fn allowedMethods<P>(&self, route: P) -> Vec<Method>
+	where
+		P: AsRef<str>,
+	{
+		let route = stripTrailingSlash(route.as_ref());
+		let mut methods = self
+			.routes
+			.iter()
+			.filter(|&(_, recognizer)| recognizer.recognize(route).is_ok())
+			.map(|(method, _)| method.clone())
+			.collect::<Vec<_>>();
+
+		if methods.is_empty() {
+			return methods;
+		}
+
+		if methods.contains(&Method::GET) && !methods.contains(&Method::HEAD) {
+			methods.push(Method::HEAD);
+		}
+		if !methods.contains(&Method::OPTIONS) {
+			methods.push(Method::OPTIONS);
+		}
+		methods.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+		methods
+	}
+
+	/// Runs the response middleware of the router on the responses it builds
+	/// itself, e.g 404s, so they get the same headers (e.g CORS) as the routes.
+	fn respond(
+		&self,
+		head: Option<Arc<RequestHead>>,
+		responseFuture: FutureResponse,
+	) -> FutureResponse {
+		let after = match self.after {
+			Some(ref after) => after.clone(),
+			None => return responseFuture,
+		};
+
+		Box::new(responseFuture.then(move |res| {
+			match res {
+				Ok(res) | Err(res) => after.call(res.carry_request(head.as_ref())),
+			}
+		}))
+	}
 }
 
 impl ArcService for Router {
-	fn call(&self, req: Request, res: Response) -> FutureResponse {
+	fn call(&self, mut req: Request, res: Response) -> FutureResponse {
+		req.remove::<BeforeRouting>();
+		if let Some(limit) = self.body_limit {
+			if let Err(res) = apply_body_limit(&mut req, limit) {
+				return Box::new(future::err(res));
+			}
+		}
+
 		if let Some(routeMatch) = self.matchRoute(req.path(), req.method()) {
 			let mut request: Request = req.into();
 			request.set(routeMatch.params);
+
+			// a `BodyParser` mounted before routing left the body for now, it's
+			// read with the route's limit.
+			if request.remove::<DeferredBody>().is_some() {
+				if let Some(limit) = routeMatch.handler.route_body_limit() {
+					if let Err(res) = apply_body_limit(&mut request, limit) {
+						return Box::new(future::err(res));
+					}
+				}
+				let handler = routeMatch.handler.clone();
+				let responseFuture = BodyParser
+					.call(request)
+					.and_then(move |request| ArcService::call(&handler, request, res));
+				return Box::new(responseFuture);
+			}
+
 			return ArcService::call(&*routeMatch.handler, request, res);
-		} else {
-			if let Some(ref notFound) = self.notFound {
-				info!(
-					"No service registered for route {} and method {}",
-					req.path(),
-					req.method()
+		}
+
+		// run the GET handler for HEAD requests, but don't send the body.
+		if *req.method() == Method::HEAD {
+			if let Some(routeMatch) = self.matchRoute(req.path(), &Method::GET) {
+				let mut request: Request = req.into();
+				request.set(routeMatch.params);
+				let responseFuture = ArcService::call(&*routeMatch.handler, request, res).then(
+					|result| {
+						match result {
+							Ok(res) => Ok(withoutBody(res)),
+							Err(res) => Err(withoutBody(res)),
+						}
+					},
 				);
-				return notFound.call(req, res);
+				return Box::new(responseFuture);
+			}
+		}
+
+		let head = res.request_head();
+		let allowed = self.allowedMethods(req.path());
+		if !allowed.is_empty() {
+			let allow = allowed
+				.iter()
+				.map(|method| method.as_str())
+				.collect::<Vec<_>>()
+				.join(", ");
+			let allow = HeaderValue::from_str(&allow).unwrap();
+
+			if *req.method() == Method::OPTIONS {
+				let mut res = res;
+				res.headers_mut().insert(ALLOW, allow);
+				return self.respond(head, Box::new(Ok(res).into_future()));
 			}
-			let responseFuture = Ok(Response::new().with_status(404)).into_future();
 
-			return Box::new(responseFuture);
+			info!(
+				"Method {} not allowed for route {}",
+				req.method(),
+				req.path()
+			);
+			let mut res = res.methodNotAllowed();
+			res.headers_mut().insert(ALLOW, allow);
+
+			if let Some(ref methodNotAllowed) = self.methodNotAllowed {
+				return self.respond(head, methodNotAllowed.call(req, res));
+			}
+
+			return self.respond(head, Box::new(Ok(res).into_future()));
+		}
+
+		if let Some(ref notFound) = self.notFound {
+			info!(
+				"No service registered for route {} and method {}",
+				req.path(),
+				req.method()
+			);
+			return self.respond(head, notFound.call(req, res));
+		}
+		let responseFuture = Ok(res.with_status(404)).into_future();
+
+		return self.respond(head, Box::new(responseFuture));
+	}
+}
+
+/// Drops the body of a response to a HEAD request, keeping the Content-Length
+/// the GET response would have had.
+fn withoutBody(mut res: Response) -> Response {
+	if !res.headers().contains_key(CONTENT_LENGTH) {
+		if let Some(len) = res.body_ref().content_length() {
+			res.headers_mut()
+				.insert(CONTENT_LENGTH, HeaderValue::from(len));
+		}
+	}
+
+	res.with_body(Body::empty())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use contrib::{BodyLimit, FakeReactor};
+	use http::request::Builder;
+	use hyper::{header::CONTENT_TYPE, HeaderMap, StatusCode};
+	use proto::MiddleWareFuture;
+	use routing::Route;
+
+	fn AsyncService(_req: Request, res: Response) -> Response {
+		res.with_text("Hello World")
+	}
+
+	fn ImportService(req: Request, res: Response) -> Response {
+		match req.json::<Vec<u8>>() {
+			Ok(bytes) => res.with_text(bytes.len().to_string()),
+			Err(_) => res.badRequest(),
+		}
+	}
+
+	fn pass(req: Request) -> MiddleWareFuture<Request> {
+		Box::new(future::ok(req))
+	}
+
+	fn deny(_req: Request) -> MiddleWareFuture<Request> {
+		Box::new(future::err(Response::new().unauthorized()))
+	}
+
+	fn created(res: Response) -> MiddleWareFuture<Response> {
+		Box::new(future::ok(res.with_status(201)))
+	}
+
+	fn call(router: &Router, method: Method, path: &str) -> StatusCode {
+		let req: Request = Builder::new()
+			.method(method)
+			.uri(path)
+			.body(Body::empty())
+			.unwrap()
+			.into();
+		let res = Response::for_request(&req);
+		match router.call(req, res).wait() {
+			Ok(res) | Err(res) => res.status(),
 		}
 	}
+
+	#[test]
+	fn it_registers_routes_for_custom_methods() {
+		let propfind = Method::from_bytes(b"PROPFIND").unwrap();
+		let router = Router::new().route(propfind.clone(), "/files", AsyncService);
+
+		assert_eq!(call(&router, propfind, "/files"), StatusCode::OK);
+		assert_eq!(
+			call(&router, Method::GET, "/files"),
+			StatusCode::METHOD_NOT_ALLOWED
+		);
+	}
+
+	#[test]
+	fn it_registers_routes_for_all_standard_methods() {
+		let router = Router::new().any("/files", AsyncService);
+
+		for method in METHODS.iter() {
+			assert_eq!(call(&router, method.clone(), "/files"), StatusCode::OK);
+		}
+		let propfind = Method::from_bytes(b"PROPFIND").unwrap();
+		assert_eq!(
+			call(&router, propfind, "/files"),
+			StatusCode::METHOD_NOT_ALLOWED
+		);
+	}
+
+	#[test]
+	#[allow(deprecated)]
+	fn the_deprecated_methods_still_mount_middlewares() {
+		let group = RouteGroup::new("admin").delete2("/users", deny, AsyncService);
+		let router = Router::new()
+			.get2("/users", deny, AsyncService)
+			.post4("/users", AsyncService, created)
+			.put3("/users", pass, AsyncService, created)
+			.group(group);
+
+		assert_eq!(
+			call(&router, Method::GET, "/users"),
+			StatusCode::UNAUTHORIZED
+		);
+		assert_eq!(call(&router, Method::POST, "/users"), StatusCode::CREATED);
+		assert_eq!(call(&router, Method::PUT, "/users"), StatusCode::CREATED);
+		assert_eq!(
+			call(&router, Method::DELETE, "/admin/users"),
+			StatusCode::UNAUTHORIZED
+		);
+	}
+
+	#[test]
+	fn it_reads_the_body_with_the_innermost_limit() {
+		let router = Router::new()
+			.body_limit(128)
+			.post("/comments", ImportService)
+			.post("/import", Route::new(ImportService).body_limit(4096));
+		// the way the `ArcReactor` serves it, with a global limit.
+		let reactor = FakeReactor::new(ArcHandler {
+			before: Some(mw![BeforeRouting, BodyLimit(64), BodyParser]),
+			handler: Some(Box::new(router)),
+			after: None,
+		});
+		let json = || {
+			let mut headers = HeaderMap::new();
+			headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+			headers
+		};
+
+		// 101 bytes of json, over the global limit but not the router's.
+		let res = reactor
+			.post("/comments", Some(vec![1u8; 50]), Some(json()))
+			.unwrap();
+		assert_eq!(res.status(), StatusCode::OK);
+
+		// 201 bytes of json, over the router's limit but not the route's.
+		let res = reactor
+			.post("/comments", Some(vec![1u8; 100]), Some(json()))
+			.unwrap_err();
+		assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+		let res = reactor
+			.post("/import", Some(vec![1u8; 100]), Some(json()))
+			.unwrap();
+		assert_eq!(res.status(), StatusCode::OK);
+
+		// rejected on the Content-Length, the body isn't read.
+		let mut headers = json();
+		headers.insert(CONTENT_LENGTH, HeaderValue::from_static("8192"));
+		let res = reactor
+			.post("/import", None::<u8>, Some(headers))
+			.unwrap_err();
+		assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+	}
 }
@@ -1,3 +1,18 @@
+use hyper::Method;
+
+/// The standard HTTP methods, used to register a route for all of them.
+pub(crate) const METHODS: [Method; 9] = [
+	Method::GET,
+	Method::HEAD,
+	Method::POST,
+	Method::PUT,
+	Method::DELETE,
+	Method::CONNECT,
+	Method::OPTIONS,
+	Method::TRACE,
+	Method::PATCH,
+];
+
 /// Removes any '/' that may exist as the last character in a string.
 pub(crate) fn stripTrailingSlash(string: &str) -> &str {
 	let len = string.chars().count();